@@ -1,65 +1,277 @@
 use std::fs;
+use std::fmt;
+use std::collections::HashSet;
+use super::utils;
 
-pub fn part1(line: &str) -> Option<u32> {
-    let mut stack = vec![];
-    for c in line.chars() {
-        match &c {
-            '(' | '[' | '{' | '<' => stack.push(c),
-            ')' => {
-                if stack.pop() != Some('(') { return Some(3); }
-            },
-            ']' => {
-                if stack.pop() != Some('[') { return Some(57); }
-            },
-            '}' => {
-                if stack.pop() != Some('{') { return Some(1197); }
-            },
-            '>' => {
-                if stack.pop() != Some('<') { return Some(25137); }
-            },
-            _ => println!("Unknown char {}", c)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Bracket {
+    pub open: char,
+    pub close: char,
+    // points for a line corrupted by this closing bracket
+    pub corrupted: u64,
+    // points for this closing bracket in a completion
+    pub completion: u64,
+}
+
+// A set of bracket pairs with their score tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Language {
+    brackets: Vec<Bracket>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckResult {
+    Valid,
+    // columns count from 0; `expected` is None when nothing was left to close
+    Corrupted { column: usize, expected: Option<char>, found: char },
+    Incomplete { completion: String },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CheckError {
+    UnknownChar { column: usize, found: char },
+}
+
+// A single character change to a line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Replace { column: usize, with: char },
+    Delete { column: usize },
+    Insert { column: usize, with: char },
+}
+
+impl Edit {
+
+    pub fn apply(&self, line: &str) -> String {
+        let mut chars: Vec<char> = line.chars().collect();
+        match *self {
+            Edit::Replace { column, with } => chars[column] = with,
+            Edit::Delete { column } => { chars.remove(column); },
+            Edit::Insert { column, with } => chars.insert(column, with),
         }
+        chars.into_iter().collect()
     }
-    None
+
 }
 
-pub fn part2(line: &str) -> Option<usize> {
-    let mut stack = vec![];
-    for c in line.chars() {
-        match &c {
-            '(' | '[' | '{' | '<' => stack.push(c),
-            ')' => {
-                if stack.pop() != Some('(') { return None; }
-            },
-            ']' => {
-                if stack.pop() != Some('[') { return None; }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    Valid,
+    // the line followed by its completion
+    Completed(String),
+    // edits which get past the corruption, with the edited and completed line
+    Fixes(Vec<(Edit, String)>),
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckError::UnknownChar { column, found } => write!(f, "Unknown char '{}' at column {}", found, column),
+        }
+    }
+}
+
+impl Language {
+
+    // pairs given as consecutive open/close characters, ex: "()[]", scoring
+    // nothing when corrupted and their position (from 1) in completions
+    pub fn new(pairs: &str) -> Language {
+        let chars: Vec<char> = pairs.chars().collect();
+        assert!(chars.len().is_multiple_of(2), "Unmatched bracket in {}", pairs);
+        let brackets: Vec<Bracket> = chars.chunks(2).enumerate()
+            .map(|(i, p)| Bracket { open: p[0], close: p[1], corrupted: 0, completion: i as u64 + 1 })
+            .collect();
+        for (i, b) in brackets.iter().enumerate() {
+            assert!(b.open != b.close, "Bracket {} opens and closes with the same char", b.open);
+            assert!(brackets[..i].iter().all(|o| ![o.open, o.close].contains(&b.open) && ![o.open, o.close].contains(&b.close)),
+                "Brackets {}{} are already used", b.open, b.close);
+        }
+        Language { brackets }
+    }
+
+    pub fn with_scores(mut self, corrupted: &[u64], completion: &[u64]) -> Language {
+        assert_eq!(corrupted.len(), self.brackets.len(), "One corrupted score needed per pair");
+        assert_eq!(completion.len(), self.brackets.len(), "One completion score needed per pair");
+        for (i, b) in self.brackets.iter_mut().enumerate() {
+            b.corrupted = corrupted[i];
+            b.completion = completion[i];
+        }
+        self
+    }
+
+    pub fn standard() -> Language {
+        Language::new("()[]{}<>").with_scores(&[3, 57, 1197, 25137], &[1, 2, 3, 4])
+    }
+
+    pub fn brackets(&self) -> &[Bracket] {
+        &self.brackets
+    }
+
+    fn opening(&self, c: char) -> Option<&Bracket> {
+        self.brackets.iter().find(|b| b.open == c)
+    }
+
+    fn closing(&self, c: char) -> Option<&Bracket> {
+        self.brackets.iter().find(|b| b.close == c)
+    }
+
+    pub fn check(&self, line: &str) -> Result<CheckResult, CheckError> {
+        let mut stack: Vec<&Bracket> = vec![];
+        for (column, c) in line.chars().enumerate() {
+            if let Some(b) = self.opening(c) {
+                stack.push(b);
+            } else if self.closing(c).is_some() {
+                match stack.pop() {
+                    Some(b) if b.close == c => (),
+                    other => return Ok(CheckResult::Corrupted { column, expected: other.map(|b| b.close), found: c }),
+                }
+            } else {
+                return Err(CheckError::UnknownChar { column, found: c });
+            }
+        }
+        match stack.is_empty() {
+            true => Ok(CheckResult::Valid),
+            false => Ok(CheckResult::Incomplete { completion: stack.iter().rev().map(|b| b.close).collect() }),
+        }
+    }
+
+    pub fn repair(&self, line: &str) -> Result<Repair, CheckError> {
+        let (column, expected) = match self.check(line)? {
+            CheckResult::Valid => return Ok(Repair::Valid),
+            CheckResult::Incomplete { completion } => return Ok(Repair::Completed(format!("{}{}", line, completion))),
+            CheckResult::Corrupted { column, expected, .. } => (column, expected),
+        };
+        // Only an edit up to the corrupted column can get past it. Working
+        // back from there: the expected closer first, then any other bracket,
+        // dropping the character, or adding a bracket before it.
+        let mut edits: Vec<Edit> = expected.iter().map(|&with| Edit::Replace { column, with }).collect();
+        let chars: Vec<char> = line.chars().collect();
+        let all: Vec<char> = self.brackets.iter().map(|b| b.close).chain(self.brackets.iter().map(|b| b.open)).collect();
+        for c in (0..=column).rev() {
+            edits.extend(all.iter().filter(|&&with| with != chars[c] && (c != column || Some(with) != expected))
+                .map(|&with| Edit::Replace { column: c, with }));
+            edits.push(Edit::Delete { column: c });
+            edits.extend(all.iter().map(|&with| Edit::Insert { column: c, with }));
+        }
+
+        let mut fixes: Vec<(Edit, String)> = vec![];
+        let mut seen = HashSet::new();
+        for edit in edits {
+            // different edits can give the same line, ex: deleting either of two equal characters
+            let edited = edit.apply(line);
+            if !seen.insert(edited.clone()) {
+                continue;
+            }
+            match self.check(&edited)? {
+                CheckResult::Valid => fixes.push((edit, edited)),
+                CheckResult::Incomplete { completion } => fixes.push((edit, edited + &completion)),
+                CheckResult::Corrupted { .. } => (),
+            }
+        }
+        Ok(Repair::Fixes(fixes))
+    }
+
+    // One chunk per line, with the chunks inside it indented by `indent`
+    // spaces. Empty chunks stay on a single line.
+    pub fn format(&self, line: &str, indent: usize) -> Result<String, CheckError> {
+        self.check(line)?;
+        let chars: Vec<char> = line.chars().collect();
+        let mut output = vec![];
+        let mut depth: usize = 0;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match self.opening(c) {
+                Some(b) if chars.get(i + 1) == Some(&b.close) => {
+                    output.push(format!("{}{}{}", " ".repeat(depth * indent), b.open, b.close));
+                    i += 1;
+                },
+                Some(_) => {
+                    output.push(format!("{}{}", " ".repeat(depth * indent), c));
+                    depth += 1;
+                },
+                None => {
+                    depth = depth.saturating_sub(1);
+                    output.push(format!("{}{}", " ".repeat(depth * indent), c));
+                },
+            }
+            i += 1;
+        }
+        Ok(output.join("\n") + "\n")
+    }
+
+    pub fn corrupted_score(&self, result: &CheckResult) -> Option<u64> {
+        match result {
+            CheckResult::Corrupted { found, .. } => self.closing(*found).map(|b| b.corrupted),
+            _ => None,
+        }
+    }
+
+    pub fn completion_score(&self, result: &CheckResult) -> Option<u64> {
+        match result {
+            CheckResult::Incomplete { completion } => Some(completion.chars()
+                .fold(0, |total, c| total * 5 + self.closing(c).unwrap().completion)),
+            _ => None,
+        }
+    }
+
+}
+
+pub fn part1(line: &str) -> Result<Option<u32>, CheckError> {
+    let language = Language::standard();
+    let result = language.check(line)?;
+    Ok(language.corrupted_score(&result).map(|s| s as u32))
+}
+
+pub fn part2(line: &str) -> Result<Option<usize>, CheckError> {
+    let language = Language::standard();
+    let result = language.check(line)?;
+    Ok(language.completion_score(&result).map(|s| s as usize))
+}
+
+fn describe(edit: &Edit) -> String {
+    match edit {
+        Edit::Replace { column, with } => format!("replace column {} with '{}'", column, with),
+        Edit::Delete { column } => format!("delete column {}", column),
+        Edit::Insert { column, with } => format!("insert '{}' at column {}", with, column),
+    }
+}
+
+// print the repair of each line, saving the repaired lines if asked to
+fn report_repairs(language: &Language, lines: &[&str], args: &[String]) {
+    let mut repaired = vec![];
+    for (i, l) in lines.iter().enumerate() {
+        match language.repair(l).unwrap() {
+            Repair::Valid => {
+                println!("Line {}: valid", i + 1);
+                repaired.push(l.to_string());
             },
-            '}' => {
-                if stack.pop() != Some('{') { return None; }
+            Repair::Completed(line) => {
+                println!("Line {}: completed with {}", i + 1, &line[l.len()..]);
+                repaired.push(line);
             },
-            '>' => {
-                if stack.pop() != Some('<') { return None; }
+            Repair::Fixes(fixes) => {
+                println!("Line {}: corrupted, {}", i + 1, match fixes.is_empty() {
+                    true => String::from("no single character fix"),
+                    false => fixes.iter().map(|(e, _)| describe(e)).collect::<Vec<_>>().join(" or "),
+                });
+                repaired.push(fixes.first().map_or(l.to_string(), |(_, line)| line.clone()));
             },
-            _ => println!("Unknown char {}", c)
         }
     }
-    let mut total = 0;
-    while let Some(c) = stack.pop() {
-        let num = match &c {
-            '(' => 1,
-            '[' => 2,
-            '{' => 3,
-            '<' => 4,
-            _ => { println!("Unknown char {}", c); 0 }
-        };
-        total = total * 5 + num;
+    if let Some(filename) = utils::option(args, "--output") {
+        fs::write(filename, repaired.join("\n") + "\n").expect("Unable to write repaired lines");
+        println!("Wrote {}", filename);
     }
-    Some(total)
+}
+
+fn scores(list: &str) -> Vec<u64> {
+    list.split(',').map(|s| s.parse().unwrap()).collect()
 }
 
 pub fn day10(args: &[String]) -> i32 {
     println!("Day 10");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -68,10 +280,48 @@ pub fn day10(args: &[String]) -> i32 {
     let contents = fs::read_to_string(filename)
         .expect("Something went wrong reading the file");
 
-    println!("Part 1: {}", contents.lines().filter_map(|l| part1(l)).sum::<u32>());
-    let mut p = contents.lines().filter_map(|l| part2(l)).collect::<Vec<_>>();
+    // ex: --pairs "()<>" --scores 3,25137 --completion 1,4
+    let language = match utils::option(args, "--pairs") {
+        Some(pairs) => {
+            let language = Language::new(pairs);
+            let n = language.brackets().len();
+            let corrupted = utils::option(args, "--scores").map_or(vec![0; n], scores);
+            let completion = utils::option(args, "--completion").map_or((1..=n as u64).collect(), scores);
+            language.with_scores(&corrupted, &completion)
+        },
+        None => Language::standard(),
+    };
+
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut results = vec![];
+    for (i, l) in lines.iter().enumerate() {
+        match language.check(l) {
+            Ok(r) => results.push(r),
+            Err(e) => {
+                println!("Line {}: {}", i + 1, e);
+                return -1;
+            },
+        }
+    }
+
+    println!("Part 1: {}", results.iter().filter_map(|r| language.corrupted_score(r)).sum::<u64>());
+    let mut p = results.iter().filter_map(|r| language.completion_score(r)).collect::<Vec<_>>();
     p.sort();
-    println!("Part 2: {:?}", p[p.len()/2]);
-    
+    match p.is_empty() {
+        true => println!("Part 2: no incomplete lines"),
+        false => println!("Part 2: {}", p[p.len() / 2]),
+    }
+
+    if utils::flag(args, "--repair") {
+        report_repairs(&language, &lines, args);
+    }
+    // --format [indent], two spaces by default
+    if utils::flag(args, "--format") {
+        let indent = utils::option(args, "--format").and_then(|i| i.parse().ok()).unwrap_or(2);
+        for l in &lines {
+            println!("{}", language.format(l, indent).unwrap());
+        }
+    }
+
     0
 }
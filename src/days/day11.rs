@@ -1,11 +1,128 @@
 use std::fs;
 use std::fmt;
+use std::collections::HashMap;
 use itertools::iproduct;
+use super::utils;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Neighbourhood {
+    Orthogonal,
+    Moore,
+    Offsets(Vec<(isize, isize)>),
+}
+
+impl Neighbourhood {
+
+    pub fn offsets(&self) -> Vec<(isize, isize)> {
+        match self {
+            Neighbourhood::Orthogonal => ADJ[..4].to_vec(),
+            Neighbourhood::Moore => ADJ.clone(),
+            Neighbourhood::Offsets(o) => o.clone(),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Neighbourhood> {
+        match name {
+            "orthogonal" | "4" => Some(Neighbourhood::Orthogonal),
+            "moore" | "8" => Some(Neighbourhood::Moore),
+            _ => None,
+        }
+    }
+
+}
+
+// An octopus flashes once its energy reaches `threshold`, giving one unit to
+// each neighbour, and ends the step at `reset`. With `wrap` the grid is a
+// torus so the neighbourhood continues on the opposite edge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashRules {
+    pub threshold: u32,
+    pub neighbourhood: Neighbourhood,
+    pub wrap: bool,
+    pub reset: u32,
+}
+
+impl FlashRules {
+
+    pub fn new() -> FlashRules {
+        FlashRules { threshold: 10, neighbourhood: Neighbourhood::Moore, wrap: false, reset: 0 }
+    }
+
+    pub fn with_threshold(mut self, threshold: u32) -> FlashRules {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_neighbourhood(mut self, neighbourhood: Neighbourhood) -> FlashRules {
+        self.neighbourhood = neighbourhood;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: bool) -> FlashRules {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_reset(mut self, reset: u32) -> FlashRules {
+        self.reset = reset;
+        self
+    }
+
+}
+
+impl Default for FlashRules {
+    fn default() -> Self {
+        FlashRules::new()
+    }
+}
+
+// `cell` flashed during `step` (counted from 1), set off by a flash of the
+// previous `wave`, or by the step itself for wave 0
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FlashEvent {
+    pub step: usize,
+    pub wave: usize,
+    pub cell: (usize, usize),
+}
+
+// The energy levels repeat every `length` steps once `start` steps have
+// passed, with the running total of flashes until then.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+    // flashes in the first n steps, for n up to start + length
+    total_flashes: Vec<u128>,
+    cells: usize,
+}
+
+impl Cycle {
+
+    pub fn flashes_after(&self, steps: usize) -> u128 {
+        let total = &self.total_flashes;
+        if steps < total.len() {
+            return total[steps];
+        }
+        let (full, rem) = ((steps - self.start) / self.length, (steps - self.start) % self.length);
+        let per_cycle = total[self.start + self.length] - total[self.start];
+        total[self.start + rem] + full as u128 * per_cycle
+    }
+
+    // the first step in which every octopus flashes, if there ever is one
+    pub fn first_sync(&self) -> Option<usize> {
+        self.total_flashes.windows(2).position(|w| w[1] - w[0] == self.cells as u128).map(|s| s + 1)
+    }
+
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EnergyMap {
     map: Vec<Vec<u32>>,
     flashes: usize,
+    rules: FlashRules,
+    steps: usize,
+    // only recorded once enabled with with_log
+    log: Option<Vec<FlashEvent>>,
 }
 
 lazy_static! {
@@ -18,7 +135,7 @@ lazy_static! {
 impl EnergyMap {
 
     pub fn new(map: Vec<Vec<u32>>) -> EnergyMap {        
-        EnergyMap { map, flashes: 0 }
+        EnergyMap { map, flashes: 0, rules: FlashRules::new(), steps: 0, log: None }
     }
 
     pub fn parse(input :&str) -> EnergyMap {
@@ -26,49 +143,114 @@ impl EnergyMap {
             .map(|l| l.chars().map(|c| c.to_digit(10).unwrap()).collect()).collect())
     }
 
+    pub fn with_rules(mut self, rules: FlashRules) -> EnergyMap {
+        self.rules = rules;
+        self
+    }
+
+    pub fn with_log(mut self) -> EnergyMap {
+        self.log = Some(vec![]);
+        self
+    }
+
     pub fn size(&self) -> (usize, usize) {
         (self.map.len(), self.map[0].len())
     }
 
+    pub fn flashes(&self) -> usize {
+        self.flashes
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn events(&self) -> &[FlashEvent] {
+        self.log.as_deref().unwrap_or(&[])
+    }
+
+    // return true if all flashed this round
     pub fn next(&mut self) -> bool {
-        self.map.iter_mut().flatten().for_each(|v| *v += 1);
         let (my, mx) = self.size();
-        let mut count = 0;
-        loop {
-            // loop until there are no more flashes
-            let mut flashed = false;
-            for (y, x) in iproduct!(0..my, 0..mx) {
-                if self.map[y][x] == 0 {
-                    continue;
-                }
-                if self.map[y][x] >= 10 {
-                    self.map[y][x] = 0;
-                    count += 1;
-                    flashed = true;
-                    // flash adjacent
-                    self.adjacent(y as isize, x as isize).iter()
-                        .for_each(|&(y, x)| {
-                            // increment if haven't already flashed
-                            if self.map[y as usize][x as usize] > 0 {
-                                self.map[y as usize][x as usize] += 1;
-                            }
-                        });
+        self.step().iter().map(|w| w.len()).sum::<usize>() == my * mx
+    }
+
+    // Advance one step, returning the cells which flashed in each wave
+    pub fn step(&mut self) -> Vec<Vec<(usize, usize)>> {
+        let (my, mx) = self.size();
+        let threshold = self.rules.threshold;
+        self.map.iter_mut().flatten().for_each(|v| *v += 1);
+        let mut flashed = vec![vec![false; mx]; my];
+        let mut wave: Vec<(usize, usize)> = iproduct!(0..my, 0..mx)
+            .filter(|&(y, x)| self.map[y][x] >= threshold)
+            .collect();
+        wave.iter().for_each(|&(y, x)| flashed[y][x] = true);
+        let mut waves = vec![];
+        while !wave.is_empty() {
+            let mut next = vec![];
+            for &(y, x) in &wave {
+                for (ny, nx) in self.adjacent(y as isize, x as isize) {
+                    let (ny, nx) = (ny as usize, nx as usize);
+                    if flashed[ny][nx] {
+                        continue;
+                    }
+                    self.map[ny][nx] += 1;
+                    if self.map[ny][nx] >= threshold {
+                        flashed[ny][nx] = true;
+                        next.push((ny, nx));
+                    }
                 }
             }
-            if !flashed {
-                break
+            waves.push(wave);
+            wave = next;
+        }
+
+        self.steps += 1;
+        for (i, w) in waves.iter().enumerate() {
+            for &(y, x) in w {
+                self.map[y][x] = self.rules.reset;
+            }
+            self.flashes += w.len();
+            if let Some(log) = &mut self.log {
+                log.extend(w.iter().map(|&cell| FlashEvent { step: self.steps, wave: i, cell }));
+            }
+        }
+        waves
+    }
+
+    // Step a copy of the map until its energy levels repeat, giving up after
+    // max_steps
+    pub fn find_cycle(&self, max_steps: usize) -> Option<Cycle> {
+        let mut em = self.clone();
+        em.log = None;
+        let (my, mx) = em.size();
+        let mut seen = HashMap::from([(em.map.clone(), 0)]);
+        let mut total_flashes = vec![0];
+        for step in 1..=max_steps {
+            let flashes: usize = em.step().iter().map(|w| w.len()).sum();
+            total_flashes.push(total_flashes[step - 1] + flashes as u128);
+            if let Some(start) = seen.insert(em.map.clone(), step) {
+                return Some(Cycle { start, length: step - start, total_flashes, cells: my * mx });
             }
         }
-        self.flashes += count;
-        // return true if all flashed this round
-        count == my * mx
+        None
     }
 
+    // the distinct neighbours of a cell under the current rules
     pub fn adjacent(&self, y: isize, x: isize) -> Vec<(isize, isize)> {
-        let (my, mx) = self.size();
-        ADJ.iter().map(|(dy, dx)| (y + dy, x + dx))
-            .filter(|&(y, x)| y >= 0 && x >= 0 && y < my as isize && x < mx as isize)
-            .collect()
+        let (my, mx) = (self.size().0 as isize, self.size().1 as isize);
+        let mut cells: Vec<(isize, isize)> = self.rules.neighbourhood.offsets().iter()
+            .map(|(dy, dx)| (y + dy, x + dx))
+            .filter_map(|(ny, nx)| match self.rules.wrap {
+                true => Some((ny.rem_euclid(my), nx.rem_euclid(mx))),
+                false if ny >= 0 && nx >= 0 && ny < my && nx < mx => Some((ny, nx)),
+                false => None,
+            })
+            .filter(|&c| c != (y, x))
+            .collect();
+        cells.sort_unstable();
+        cells.dedup();
+        cells
     }
 
 }
@@ -92,7 +274,7 @@ impl fmt::Display for EnergyMap {
 
 pub fn day11(args: &[String]) -> i32 {
     println!("Day 11");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -101,13 +283,63 @@ pub fn day11(args: &[String]) -> i32 {
     let contents = fs::read_to_string(filename)
         .expect("Something went wrong reading the file");
 
-    let mut em = EnergyMap::parse(&contents);
+    let mut rules = FlashRules::new().with_wrap(utils::flag(args, "--wrap"));
+    if let Some(threshold) = utils::option(args, "--threshold") {
+        rules = rules.with_threshold(threshold.parse().unwrap());
+    }
+    if let Some(reset) = utils::option(args, "--reset") {
+        rules = rules.with_reset(reset.parse().unwrap());
+    }
+    if let Some(name) = utils::option(args, "--neighbourhood") {
+        match Neighbourhood::parse(name) {
+            Some(neighbourhood) => rules = rules.with_neighbourhood(neighbourhood),
+            None => {
+                println!("Unknown neighbourhood: {}", name);
+                return -1;
+            },
+        }
+    }
+    let start = EnergyMap::parse(&contents).with_rules(rules);
+
+    let mut em = start.clone();
     (0..100).for_each(|_| { em.next(); } );
     println!("Part 1: {}", em.flashes);
 
-    em = EnergyMap::parse(&contents);
-    let mut steps = (1..).skip_while(|_| !em.next());
-    println!("Part 2: {}", steps.next().unwrap());
+    // --events N prints the flash waves of the first N steps
+    if let Some(steps) = utils::option(args, "--events") {
+        let mut em = start.clone().with_log();
+        (0..steps.parse().unwrap()).for_each(|_| { em.next(); });
+        for e in em.events() {
+            println!("Step {} wave {}: {:?}", e.step, e.wave, e.cell);
+        }
+    }
+
+    let limit = utils::option(args, "--limit").map_or(100_000, |l| l.parse().unwrap());
+    let cycle = match start.find_cycle(limit) {
+        Some(c) => c,
+        None => {
+            println!("No cycle within {} steps", limit);
+            return -1;
+        },
+    };
+    match cycle.first_sync() {
+        Some(step) => println!("Part 2: {}", step),
+        None => println!("Part 2: the octopuses never flash together"),
+    }
+    println!("Cycle of {} steps after step {}", cycle.length, cycle.start);
+    if let Some(steps) = utils::option(args, "--steps") {
+        println!("Flashes after {} steps: {}", steps, cycle.flashes_after(steps.parse().unwrap()));
+    }
+
+    // play the steps up to the first synchronised flash, or 100 steps
+    if let Some(mut animation) = utils::Animation::from_args(args) {
+        let mut em = start.clone();
+        animation.show("Step 0", &em.to_string());
+        for step in 1..=cycle.first_sync().unwrap_or(100) {
+            em.next();
+            animation.show(&format!("Step {}, {} flashes", step, em.flashes()), &em.to_string());
+        }
+    }
     
     0
 }
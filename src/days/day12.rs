@@ -1,5 +1,8 @@
 use std::fs;
-use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::collections::{HashMap, HashSet};
+use super::utils;
 
 #[derive(Debug, Hash, Clone, PartialEq, Eq)]
 pub struct Cave {
@@ -34,6 +37,426 @@ impl Cave {
 
 }
 
+// Which paths through the caves are allowed: small caves may be visited up
+// to `small_limit` times, plus `spare` extra visits shared between them,
+// while big and `revisitable` caves may be visited any number of times.
+// start is never revisited and a path stops at end. `max_length` caps the
+// number of moves and `forbidden` connections are never taken, either way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisitPolicy {
+    pub small_limit: u32,
+    pub spare: u32,
+    pub revisitable: HashSet<String>,
+    pub max_length: Option<usize>,
+    pub forbidden: HashSet<(String, String)>,
+}
+
+impl VisitPolicy {
+
+    // small caves at most once
+    pub fn new() -> VisitPolicy {
+        VisitPolicy { small_limit: 1, spare: 0, revisitable: HashSet::new(), max_length: None, forbidden: HashSet::new() }
+    }
+
+    // a single small cave may be visited twice
+    pub fn part2() -> VisitPolicy {
+        VisitPolicy::new().with_spare(1)
+    }
+
+    pub fn with_small_limit(mut self, limit: u32) -> VisitPolicy {
+        self.small_limit = limit;
+        self
+    }
+
+    pub fn with_spare(mut self, spare: u32) -> VisitPolicy {
+        self.spare = spare;
+        self
+    }
+
+    pub fn with_revisitable(mut self, cave: &str) -> VisitPolicy {
+        self.revisitable.insert(cave.to_string());
+        self
+    }
+
+    pub fn with_max_length(mut self, moves: usize) -> VisitPolicy {
+        self.max_length = Some(moves);
+        self
+    }
+
+    pub fn with_forbidden(mut self, a: &str, b: &str) -> VisitPolicy {
+        self.forbidden.insert((a.to_string(), b.to_string()));
+        self
+    }
+
+    pub fn is_forbidden(&self, a: &Cave, b: &Cave) -> bool {
+        self.forbidden.contains(&(a.name.clone(), b.name.clone())) || self.forbidden.contains(&(b.name.clone(), a.name.clone()))
+    }
+
+}
+
+impl Default for VisitPolicy {
+    fn default() -> Self {
+        VisitPolicy::new()
+    }
+}
+
+// Visit counts packed `width` bits per limited cave, in a single word while
+// they fit and in as many as needed otherwise. A count never straddles two
+// words.
+trait Counts: Clone + Eq + Hash {
+    fn empty(bits: u32) -> Self;
+    fn get(&self, offset: u32, width: u32) -> u32;
+    fn increment(&mut self, offset: u32);
+}
+
+impl Counts for u64 {
+
+    fn empty(_bits: u32) -> u64 {
+        0
+    }
+
+    fn get(&self, offset: u32, width: u32) -> u32 {
+        ((self >> offset) & ((1 << width) - 1)) as u32
+    }
+
+    fn increment(&mut self, offset: u32) {
+        *self += 1 << offset;
+    }
+
+}
+
+impl Counts for Vec<u64> {
+
+    fn empty(bits: u32) -> Vec<u64> {
+        vec![0; bits.div_ceil(u64::BITS) as usize]
+    }
+
+    fn get(&self, offset: u32, width: u32) -> u32 {
+        self[(offset / u64::BITS) as usize].get(offset % u64::BITS, width)
+    }
+
+    fn increment(&mut self, offset: u32) {
+        self[(offset / u64::BITS) as usize].increment(offset % u64::BITS);
+    }
+
+}
+
+// Visits of the limited caves so far, capped at the limit as any further
+// visit only uses up spares. The moves are only counted with a length limit,
+// so they don't split the memo.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Visits<C> {
+    counts: C,
+    spare: u32,
+    moves: u32,
+}
+
+// A policy applied to a graph: where each limited cave's count sits in
+// Visits::counts, None for caves visited freely or never revisited
+struct Traversal<'a, 'p> {
+    graph: &'a CaveGraph,
+    policy: &'p VisitPolicy,
+    slots: Vec<Option<u32>>,
+    width: u32,
+    bits: u32,
+    forbidden: HashSet<(usize, usize)>,
+}
+
+impl<'a, 'p> Traversal<'a, 'p> {
+
+    fn new(graph: &'a CaveGraph, policy: &'p VisitPolicy) -> Traversal<'a, 'p> {
+        let width = u32::BITS - policy.small_limit.leading_zeros();
+        let mut slots = vec![None; graph.caves.len()];
+        let mut bits = 0;
+        for (id, c) in graph.caves.iter().enumerate() {
+            if !(c.is_start() || c.is_end() || c.is_big() || policy.revisitable.contains(&c.name)) {
+                if bits % u64::BITS + width > u64::BITS {
+                    bits = bits.next_multiple_of(u64::BITS);
+                }
+                slots[id] = Some(bits);
+                bits += width;
+            }
+        }
+        let forbidden = policy.forbidden.iter()
+            .filter_map(|(a, b)| Some((graph.id(a)?, graph.id(b)?)))
+            .flat_map(|(a, b)| [(a, b), (b, a)])
+            .collect();
+        Traversal { graph, policy, slots, width, bits, forbidden }
+    }
+
+    // whether the counts fit in a single word
+    fn is_narrow(&self) -> bool {
+        self.bits <= u64::BITS
+    }
+
+    fn start<C: Counts>(&self) -> (usize, Visits<C>) {
+        (self.graph.id("start").unwrap(), Visits { counts: C::empty(self.bits), spare: self.policy.spare, moves: 0 })
+    }
+
+    fn count<C: Counts>(&self) -> u64 {
+        let (start, visits) = self.start::<C>();
+        self.count_from(start, visits, &mut HashMap::new())
+    }
+
+    fn count_from<C: Counts>(&self, cave: usize, visits: Visits<C>, memo: &mut HashMap<(usize, Visits<C>), u64>) -> u64 {
+        if self.graph.caves[cave].is_end() {
+            return 1;
+        }
+        if let Some(&count) = memo.get(&(cave, visits.clone())) {
+            return count;
+        }
+        let count = self.graph.adjacent[cave].iter()
+            .filter_map(|&n| self.visit(cave, n, &visits).map(|v| self.count_from(n, v, memo)))
+            .sum();
+        memo.insert((cave, visits), count);
+        count
+    }
+
+    fn paths<C: Counts>(&self) -> Vec<Vec<&'a Cave>> {
+        let (start, visits) = self.start::<C>();
+        let mut paths = vec![];
+        self.walk(&mut vec![start], &visits, &mut paths);
+        paths
+    }
+
+    fn walk<C: Counts>(&self, path: &mut Vec<usize>, visits: &Visits<C>, paths: &mut Vec<Vec<&'a Cave>>) {
+        let cave = *path.last().unwrap();
+        if self.graph.caves[cave].is_end() {
+            paths.push(path.iter().map(|&c| &self.graph.caves[c]).collect());
+            return;
+        }
+        for &n in &self.graph.adjacent[cave] {
+            if let Some(v) = self.visit(cave, n, visits) {
+                path.push(n);
+                self.walk(path, &v, paths);
+                path.pop();
+            }
+        }
+    }
+
+    // the visits after moving from `from` to `to`, if the policy allows it
+    fn visit<C: Counts>(&self, from: usize, to: usize, visits: &Visits<C>) -> Option<Visits<C>> {
+        if self.graph.caves[to].is_start() || self.forbidden.contains(&(from, to)) {
+            return None;
+        }
+        let mut visits = visits.clone();
+        if let Some(max) = self.policy.max_length {
+            if visits.moves as usize >= max {
+                return None;
+            }
+            visits.moves += 1;
+        }
+        let Some(offset) = self.slots[to] else {
+            return Some(visits);
+        };
+        if visits.counts.get(offset, self.width) >= self.policy.small_limit {
+            // each visit past the limit uses up one of the spares
+            visits.spare = visits.spare.checked_sub(1)?;
+        } else {
+            visits.counts.increment(offset);
+        }
+        Some(visits)
+    }
+
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    // two connected caves which may both be visited without limit, the same
+    // one twice for a cave connected to itself, so a path can bounce between
+    // them forever
+    Infinite(Cave, Cave),
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathError::Infinite(a, b) => write!(f, "Infinite paths between {} and {}, limit the path length", a.name, b.name),
+        }
+    }
+}
+
+// The caves with their names interned to ids, which index `caves` and the
+// adjacency lists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaveGraph {
+    caves: Vec<Cave>,
+    ids: HashMap<String, usize>,
+    adjacent: Vec<Vec<usize>>,
+}
+
+impl CaveGraph {
+
+    pub fn parse(input: &str) -> CaveGraph {
+        let mut graph = CaveGraph { caves: vec![], ids: HashMap::new(), adjacent: vec![] };
+        for l in input.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            let (a, b) = l.split_once('-').unwrap_or_else(|| panic!("Invalid connection {}", l));
+            let (a, b) = (graph.intern(a), graph.intern(b));
+            if !graph.adjacent[a].contains(&b) {
+                graph.adjacent[a].push(b);
+                // a cave connected to itself is only listed once
+                if a != b {
+                    graph.adjacent[b].push(a);
+                }
+            }
+        }
+        assert!(graph.id("start").is_some() && graph.id("end").is_some(), "Missing start or end");
+        graph
+    }
+
+    fn intern(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        self.caves.push(Cave::new(name));
+        self.adjacent.push(vec![]);
+        self.ids.insert(name.to_string(), self.caves.len() - 1);
+        self.caves.len() - 1
+    }
+
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn cave(&self, id: usize) -> &Cave {
+        &self.caves[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.caves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.caves.is_empty()
+    }
+
+    pub fn neighbours(&self, id: usize) -> &[usize] {
+        &self.adjacent[id]
+    }
+
+    // connections in the form taken by find_paths_part1/2: never back into
+    // start nor out of end
+    pub fn connections(&self) -> HashMap<Cave, Vec<Cave>> {
+        let mut connections: HashMap<Cave, Vec<Cave>> = HashMap::new();
+        for (a, next) in self.adjacent.iter().enumerate() {
+            if self.caves[a].is_end() {
+                continue;
+            }
+            connections.entry(self.caves[a].clone()).or_default()
+                .extend(next.iter().map(|&b| self.caves[b].clone()).filter(|c| !c.is_start()));
+        }
+        connections
+    }
+
+    pub fn degree(&self, id: usize) -> usize {
+        self.adjacent[id].len()
+    }
+
+    // ids of the caves connected to start, directly or not
+    pub fn reachable(&self) -> HashSet<usize> {
+        self.spread(|_, _| true)
+    }
+
+    // ids of the caves a path allowed by the policy can get to: one which
+    // doesn't take forbidden connections, go on past end or enter a cave it
+    // may never visit
+    fn reachable_with(&self, policy: &VisitPolicy) -> HashSet<usize> {
+        let enterable = |c: &Cave| c.is_big() || policy.revisitable.contains(&c.name) || policy.small_limit + policy.spare > 0;
+        self.spread(|a, b| {
+            let (a, b) = (&self.caves[a], &self.caves[b]);
+            !a.is_end() && !b.is_start() && !policy.is_forbidden(a, b) && enterable(b)
+        })
+    }
+
+    // ids of the caves found from start following the moves allowed by `can_move`
+    fn spread(&self, can_move: impl Fn(usize, usize) -> bool) -> HashSet<usize> {
+        let start = self.id("start").unwrap();
+        let mut seen = HashSet::from([start]);
+        let mut queue = vec![start];
+        while let Some(c) = queue.pop() {
+            for &n in &self.adjacent[c] {
+                if can_move(c, n) && seen.insert(n) {
+                    queue.push(n);
+                }
+            }
+        }
+        seen
+    }
+
+    pub fn unreachable(&self) -> Vec<&Cave> {
+        let reachable = self.reachable();
+        (0..self.caves.len()).filter(|c| !reachable.contains(c)).map(|c| &self.caves[c]).collect()
+    }
+
+    // pairs of connected big caves
+    pub fn big_adjacent(&self) -> Vec<(&Cave, &Cave)> {
+        self.edges().filter(|&(a, b)| a.is_big() && b.is_big()).collect()
+    }
+
+    // each connection once, a cave connected to itself included
+    pub fn edges(&self) -> impl Iterator<Item = (&Cave, &Cave)> {
+        self.adjacent.iter().enumerate()
+            .flat_map(move |(a, next)| next.iter().filter(move |&&b| a <= b).map(move |&b| (&self.caves[a], &self.caves[b])))
+    }
+
+    // Graphviz graph of the caves: big caves boxed, start green and end red
+    pub fn to_dot(&self) -> String {
+        let mut output = vec![String::from("graph caves {")];
+        for c in &self.caves {
+            let style = match (c.is_start(), c.is_end(), c.is_big()) {
+                (true, _, _) => "shape=doublecircle, style=filled, fillcolor=palegreen",
+                (_, true, _) => "shape=doublecircle, style=filled, fillcolor=salmon",
+                (_, _, true) => "shape=box, style=bold",
+                _ => "shape=circle",
+            };
+            output.push(format!("    \"{}\" [{}];", c.name, style));
+        }
+        for (a, b) in self.edges() {
+            output.push(format!("    \"{}\" -- \"{}\";", a.name, b.name));
+        }
+        output.push(String::from("}"));
+        output.join("\n") + "\n"
+    }
+
+    // Number of paths from start to end allowed by the policy
+    pub fn count_paths(&self, policy: &VisitPolicy) -> Result<u64, PathError> {
+        self.check_finite(policy)?;
+        let traversal = Traversal::new(self, policy);
+        Ok(match traversal.is_narrow() {
+            true => traversal.count::<u64>(),
+            false => traversal.count::<Vec<u64>>(),
+        })
+    }
+
+    // paths are only endless when two unlimited caves connect, or one connects
+    // to itself, without a cap on the length, as every other move lands in a
+    // cave with limited visits. Only caves a path may get to count.
+    pub fn check_finite(&self, policy: &VisitPolicy) -> Result<(), PathError> {
+        if policy.max_length.is_some() {
+            return Ok(());
+        }
+        let unlimited = |c: &Cave| !c.is_start() && !c.is_end() && (c.is_big() || policy.revisitable.contains(&c.name));
+        let reachable = self.reachable_with(policy);
+        match self.edges()
+            .filter(|&(a, b)| reachable.contains(&self.ids[&a.name]) && reachable.contains(&self.ids[&b.name]))
+            .find(|&(a, b)| unlimited(a) && unlimited(b) && !policy.is_forbidden(a, b)) {
+            Some((a, b)) => Err(PathError::Infinite(a.clone(), b.clone())),
+            None => Ok(()),
+        }
+    }
+
+    // Every path counted by count_paths, which may be a great many
+    pub fn paths(&self, policy: &VisitPolicy) -> Result<Vec<Vec<&Cave>>, PathError> {
+        self.check_finite(policy)?;
+        let traversal = Traversal::new(self, policy);
+        Ok(match traversal.is_narrow() {
+            true => traversal.paths::<u64>(),
+            false => traversal.paths::<Vec<u64>>(),
+        })
+    }
+
+}
+
 pub fn find_paths_part1(connections: &HashMap<Cave, Vec<Cave>>, path: Vec<Cave>) -> Vec<Vec<Cave>> {
     if *path.last().unwrap() == Cave::end() {
         return vec![path.clone()];
@@ -75,7 +498,7 @@ pub fn find_paths_part2(connections: &HashMap<Cave, Vec<Cave>>, small_counts: Ha
 
 pub fn day12(args: &[String]) -> i32 {
     println!("Day 12");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -84,19 +507,60 @@ pub fn day12(args: &[String]) -> i32 {
     let contents = fs::read_to_string(filename)
         .expect("Something went wrong reading the file");
 
-    let mut connections: HashMap<Cave, Vec<Cave>> = HashMap::new();
-    contents.lines().for_each(|l| {
-        let caves: Vec<Cave> = l.split("-").map(|c| Cave::new(c)).collect();
-        if !caves[0].is_end() && !caves[1].is_start() {
-            connections.entry(caves[0].clone()).or_default().push(caves[1].clone());
+    let graph = CaveGraph::parse(&contents);
+
+    if utils::flag(args, "--analyse") {
+        for c in 0..graph.len() {
+            println!("{}: degree {}", graph.cave(c).name, graph.degree(c));
         }
-        if !caves[0].is_start() && !caves[1].is_end() {
-            connections.entry(caves[1].clone()).or_default().push(caves[0].clone());
+        println!("Connected big caves: {:?}", graph.big_adjacent().iter().map(|(a, b)| format!("{}-{}", a.name, b.name)).collect::<Vec<_>>());
+        println!("Unreachable caves: {:?}", graph.unreachable().iter().map(|c| &c.name).collect::<Vec<_>>());
+    }
+    if let Some(filename) = utils::option(args, "--dot") {
+        fs::write(filename, graph.to_dot()).expect("Unable to write graph");
+        println!("Wrote {}", filename);
+    }
+
+    let count = |policy: &VisitPolicy| match graph.count_paths(policy) {
+        Ok(count) => count.to_string(),
+        Err(e) => e.to_string(),
+    };
+    println!("Part 1: {}", count(&VisitPolicy::new()));
+    println!("Part 2: {}", count(&VisitPolicy::part2()));
+
+    // ex: --limit 2 --spare 1 --revisit a,b --max-length 10 --forbid start-A,c-d
+    let mut policy = VisitPolicy::new();
+    if let Some(limit) = utils::option(args, "--limit") {
+        policy = policy.with_small_limit(limit.parse().unwrap());
+    }
+    if let Some(spare) = utils::option(args, "--spare") {
+        policy = policy.with_spare(spare.parse().unwrap());
+    }
+    if let Some(caves) = utils::option(args, "--revisit") {
+        policy = caves.split(',').fold(policy, |p, c| p.with_revisitable(c));
+    }
+    if let Some(moves) = utils::option(args, "--max-length") {
+        policy = policy.with_max_length(moves.parse().unwrap());
+    }
+    if let Some(edges) = utils::option(args, "--forbid") {
+        policy = edges.split(',').map(|e| e.split_once('-').unwrap()).fold(policy, |p, (a, b)| p.with_forbidden(a, b));
+    }
+    if policy != VisitPolicy::new() {
+        println!("Paths with the given policy: {}", count(&policy));
+    }
+
+    // listing the paths is opt-in, there can be far too many
+    if utils::flag(args, "--paths") {
+        match graph.paths(&policy) {
+            Ok(paths) => for p in paths {
+                println!("{}", p.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(","));
+            },
+            Err(e) => {
+                println!("{}", e);
+                return -1;
+            },
         }
-    });
-   
-    println!("Part 1: {}", find_paths_part1(&connections, vec![Cave::start()]).len());
-    println!("Part 2: {}", find_paths_part2(&connections, HashMap::new(), vec![Cave::start()]).len());
+    }
 
     0
 }
@@ -1,5 +1,6 @@
 use std::fs;
 use std::fmt;
+use super::utils;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Image {
@@ -79,7 +80,7 @@ pub fn enhance(algorithm: &Vec<char>, image: &mut Image, round: u32) -> Image {
 
 pub fn day20(args: &[String]) -> i32 {
     println!("Day 20");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -100,9 +101,16 @@ pub fn day20(args: &[String]) -> i32 {
     }
     println!("Part 1: {}", part1.lit());
 
+    let mut animation = utils::Animation::from_args(args);
+    if let Some(a) = &mut animation {
+        a.show("Round 0", &image.to_string());
+    }
     let mut part2 = image.clone();
     for i in 0..50 {
         part2 = enhance(&algorithm, &mut part2, i);
+        if let Some(a) = &mut animation {
+            a.show(&format!("Round {}, {} lit", i + 1, part2.lit()), &part2.to_string());
+        }
     }
     println!("Part 2: {}", part2.lit());
     
@@ -1,4 +1,5 @@
 use std::fs;
+use std::fmt;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Rating {
@@ -6,60 +7,282 @@ pub enum Rating {
     CO2Scrubber,
 }
 
-pub fn day3(args: &[String]) -> i32 {
-    println!("Day 3");
-    if args.len() != 1 {
-        println!("Missing input file");
-        return -1;
+impl Rating {
+
+    pub fn criteria(&self) -> RatingCriteria {
+        match self {
+            Rating::OxygenGenerator => RatingCriteria::new(BitCriteria::MostCommon, true),
+            Rating::CO2Scrubber => RatingCriteria::new(BitCriteria::LeastCommon, false),
+        }
     }
-    let filename = &args[0];
-    println!("In file {}", filename);
-    let contents = fs::read_to_string(filename)
-        .expect("Something went wrong reading the file");
 
-    let entries: Vec<Vec<_>> = contents.lines()
-        .map(|l| l.chars().map(|c| c.to_digit(10).unwrap()).collect())
-        .collect();
+}
 
-    println!("Part 1: {}", part1(&entries));
-    println!("Part 2: {}", part2(&entries));
-   
-    0
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BitCriteria {
+    MostCommon,
+    LeastCommon,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanOrder {
+    LeftToRight,
+    RightToLeft,
+    Columns(Vec<usize>),
+}
+
+impl ScanOrder {
+
+    pub fn columns(&self, width: usize) -> Vec<usize> {
+        match self {
+            ScanOrder::LeftToRight => (0..width).collect(),
+            ScanOrder::RightToLeft => (0..width).rev().collect(),
+            ScanOrder::Columns(c) => c.clone(),
+        }
+    }
+
+}
+
+// Rule used to whittle the report down to a single row: at each column in
+// `order` keep the rows holding the `keep` value, or `tie` when both values
+// are equally common. A column where every remaining row agrees removes
+// nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RatingCriteria {
+    pub keep: BitCriteria,
+    pub tie: bool,
+    pub order: ScanOrder,
 }
 
-fn part1(entries: &Vec<Vec<u32>>) -> u32 {
-    let (rows, cols) = (entries.len() as u32, entries[0].len() as u32);
-    let gamma = (0..cols as usize)
-        .map(|col| entries.iter().map(|r| r[col]).sum::<u32>())
-        .map(|s| s > rows - s) 
-        .fold(0, |acc, bit| (acc << 1) | (bit as u32));
-    let epsilon = (u32::pow(2, cols) - 1) ^ gamma;
+impl RatingCriteria {
+
+    pub fn new(keep: BitCriteria, tie: bool) -> RatingCriteria {
+        RatingCriteria { keep, tie, order: ScanOrder::LeftToRight }
+    }
+
+    pub fn with_order(mut self, order: ScanOrder) -> RatingCriteria {
+        self.order = order;
+        self
+    }
+
+    pub fn keep_ones(&self, zeros: usize, ones: usize) -> bool {
+        if zeros == 0 || ones == 0 {
+            return ones > 0;
+        }
+        match (self.keep, zeros.cmp(&ones)) {
+            (_, std::cmp::Ordering::Equal) => self.tie,
+            (BitCriteria::MostCommon, order) => order.is_lt(),
+            (BitCriteria::LeastCommon, order) => order.is_gt(),
+        }
+    }
+
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FilterStep {
+    pub column: usize,
+    pub zeros: usize,
+    pub ones: usize,
+    pub kept: bool,
+    pub remaining: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection<'a> {
+    pub row: &'a BitRow,
+    pub trace: Vec<FilterStep>,
+}
+
+// A row of bits packed into 64 bit words. Column 0 is stored in the most
+// significant bit of the first word, so comparing the words compares the
+// rows the same way as comparing their binary strings.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BitRow {
+    words: Vec<u64>,
+}
+
+impl BitRow {
+
+    pub fn new(width: usize) -> BitRow {
+        BitRow { words: vec![0; width.div_ceil(64)] }
+    }
+
+    pub fn parse(line: &str) -> BitRow {
+        let mut row = BitRow::new(line.len());
+        for (col, c) in line.chars().enumerate() {
+            match c {
+                '0' => (),
+                '1' => row.set(col),
+                _ => panic!("Invalid bit '{}' in {}", c, line),
+            }
+        }
+        row
+    }
+
+    pub fn bit(&self, col: usize) -> bool {
+        (self.words[col / 64] >> (63 - col % 64)) & 1 == 1
+    }
+
+    pub fn set(&mut self, col: usize) {
+        self.words[col / 64] |= 1 << (63 - col % 64);
+    }
+
+    // value of the first `width` bits, if they fit in a u128
+    pub fn value(&self, width: usize) -> Option<u128> {
+        if width > 128 {
+            return None;
+        }
+        Some((0..width).fold(0, |acc, col| (acc << 1) | self.bit(col) as u128))
+    }
+
+    pub fn to_string(&self, width: usize) -> String {
+        (0..width).map(|col| if self.bit(col) { '1' } else { '0' }).collect()
+    }
 
-    gamma * epsilon
 }
 
-fn value_counts(entries: &Vec<Vec<u32>>, col: usize) -> (u32, u32) {
-    let ones = entries.iter().map(|r| r[col]).sum::<u32>();
-    (entries.len() as u32 - ones, ones)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    width: usize,
+    // kept sorted so rows sharing a prefix are contiguous
+    rows: Vec<BitRow>,
 }
 
-fn filter_column(entries: &Vec<Vec<u32>>, bit: usize, rating: Rating) -> u32 {
-    if entries.len() == 1 {
-        return entries[0].iter().fold(0, |acc, bit| (acc << 1) | bit);
+impl Diagnostic {
+
+    pub fn parse(input: &str) -> Diagnostic {
+        let lines: Vec<&str> = input.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+        let width = lines.first().map_or(0, |l| l.len());
+        for l in &lines {
+            assert_eq!(l.len(), width, "All rows must have the same width");
+        }
+        Diagnostic::new(width, lines.iter().map(|l| BitRow::parse(l)).collect())
+    }
+
+    pub fn new(width: usize, mut rows: Vec<BitRow>) -> Diagnostic {
+        rows.sort_unstable();
+        Diagnostic { width, rows }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn ones(&self, col: usize) -> usize {
+        self.rows.iter().filter(|r| r.bit(col)).count()
+    }
+
+    pub fn gamma(&self) -> BitRow {
+        let mut gamma = BitRow::new(self.width);
+        for col in 0..self.width {
+            let ones = self.ones(col);
+            if ones > self.len() - ones {
+                gamma.set(col);
+            }
+        }
+        gamma
+    }
+
+    pub fn epsilon(&self) -> BitRow {
+        let gamma = self.gamma();
+        let mut epsilon = BitRow::new(self.width);
+        (0..self.width).filter(|&col| !gamma.bit(col)).for_each(|col| epsilon.set(col));
+        epsilon
+    }
+
+    pub fn power_consumption(&self) -> Option<u128> {
+        self.gamma().value(self.width)?.checked_mul(self.epsilon().value(self.width)?)
+    }
+
+    pub fn rating(&self, rating: Rating) -> Option<&BitRow> {
+        self.select(&rating.criteria()).map(|s| s.row)
+    }
+
+    pub fn select(&self, criteria: &RatingCriteria) -> Option<Selection<'_>> {
+        let mut index: Vec<usize> = (0..self.rows.len()).collect();
+        let (mut lo, mut hi) = (0, index.len());
+        let mut trace = vec![];
+        // the window stays sorted while the columns are scanned left to right
+        let mut sorted = true;
+        for (i, col) in criteria.order.columns(self.width).into_iter().enumerate() {
+            assert!(col < self.width, "Column {} out of range", col);
+            if hi - lo <= 1 {
+                break;
+            }
+            sorted = sorted && col == i;
+            let window = &mut index[lo..hi];
+            let split = lo + match sorted {
+                // rows in the window share the bits before col, so the zeros come first
+                true => window.partition_point(|&r| !self.rows[r].bit(col)),
+                false => partition(window, |r| !self.rows[r].bit(col)),
+            };
+            let (zeros, ones) = (split - lo, hi - split);
+            let kept = criteria.keep_ones(zeros, ones);
+            if kept {
+                lo = split;
+            } else {
+                hi = split;
+            }
+            trace.push(FilterStep { column: col, zeros, ones, kept, remaining: hi - lo });
+        }
+        if hi == lo {
+            return None;
+        }
+        Some(Selection { row: &self.rows[index[lo]], trace })
     }
-    let (zeros, ones) = value_counts(&entries, bit);
-    let value = match rating {
-        Rating::OxygenGenerator => if ones >= zeros { 1 } else { 0 },
-        Rating::CO2Scrubber => if zeros <= ones { 0 } else { 1 },
-    };
-    let filtered = entries.iter().filter(|e| e[bit] == value).cloned().collect();
 
-    filter_column(&filtered, bit+1, rating)
+    pub fn life_support(&self) -> Option<u128> {
+        let oxygen = self.rating(Rating::OxygenGenerator)?.value(self.width)?;
+        let co2 = self.rating(Rating::CO2Scrubber)?.value(self.width)?;
+        oxygen.checked_mul(co2)
+    }
+
+}
+
+// move the entries matching pred to the front, returning how many there are
+fn partition(window: &mut [usize], pred: impl Fn(usize) -> bool) -> usize {
+    let mut split = 0;
+    for i in 0..window.len() {
+        if pred(window[i]) {
+            window.swap(split, i);
+            split += 1;
+        }
+    }
+    split
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for r in &self.rows {
+            writeln!(f, "{}", r.to_string(self.width))?;
+        }
+        Ok(())
+    }
 }
 
-fn part2(entries: &Vec<Vec<u32>>) -> u32 {
-    let oxygen = filter_column(&entries, 0, Rating::OxygenGenerator);
-    let co2 = filter_column(&entries, 0, Rating::CO2Scrubber);
-    
-    oxygen * co2
-}
\ No newline at end of file
+pub fn day3(args: &[String]) -> i32 {
+    println!("Day 3");
+    if args.len() != 1 {
+        println!("Missing input file");
+        return -1;
+    }
+    let filename = &args[0];
+    println!("In file {}", filename);
+    let contents = fs::read_to_string(filename)
+        .expect("Something went wrong reading the file");
+
+    let diagnostic = Diagnostic::parse(&contents);
+
+    println!("Part 1: {}", diagnostic.power_consumption().unwrap());
+    println!("Part 2: {}", diagnostic.life_support().unwrap());
+
+    0
+}
@@ -1,9 +1,87 @@
 use std::fs;
 use std::fmt;
+use std::collections::HashMap;
+use itertools::iproduct;
+use super::utils;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WinRule {
+    Rows,
+    Columns,
+    // both corner to corner diagonals, square boards only
+    Diagonals,
+    FourCorners,
+    FullCard,
+    // (row, col) cells which must all be marked
+    Mask(Vec<(usize, usize)>),
+}
+
+impl WinRule {
+
+    // Parse a rule name (ex: "rows", "corners" or "mask=0:0/1:1/2:2")
+    pub fn parse(rule: &str) -> Option<WinRule> {
+        match rule {
+            "rows" => Some(WinRule::Rows),
+            "columns" => Some(WinRule::Columns),
+            "diagonals" => Some(WinRule::Diagonals),
+            "corners" => Some(WinRule::FourCorners),
+            "full" => Some(WinRule::FullCard),
+            _ => {
+                let cells = rule.strip_prefix("mask=")?.split('/')
+                    .map(|c| {
+                        let (r, c) = c.split_once(':')?;
+                        Some((r.parse().ok()?, c.parse().ok()?))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some(WinRule::Mask(cells))
+            }
+        }
+    }
+
+    pub fn parse_list(rules: &str) -> Option<Vec<WinRule>> {
+        rules.split(',').map(WinRule::parse).collect()
+    }
+
+    pub fn standard() -> Vec<WinRule> {
+        vec![WinRule::Rows, WinRule::Columns]
+    }
+
+    // the groups of cells that win under this rule on a rows x cols board
+    pub fn patterns(&self, rows: usize, cols: usize) -> Vec<Vec<(usize, usize)>> {
+        let patterns = match self {
+            WinRule::Rows => (0..rows).map(|r| (0..cols).map(|c| (r, c)).collect()).collect(),
+            WinRule::Columns => (0..cols).map(|c| (0..rows).map(|r| (r, c)).collect()).collect(),
+            WinRule::Diagonals if rows == cols => vec![
+                (0..rows).map(|i| (i, i)).collect(),
+                (0..rows).map(|i| (i, cols - 1 - i)).collect(),
+            ],
+            WinRule::Diagonals => vec![],
+            WinRule::FourCorners => vec![vec![(0, 0), (0, cols - 1), (rows - 1, 0), (rows - 1, cols - 1)]],
+            WinRule::FullCard => vec![iproduct!(0..rows, 0..cols).collect()],
+            WinRule::Mask(cells) => {
+                match cells.iter().all(|&(r, c)| r < rows && c < cols) {
+                    true => vec![cells.clone()],
+                    false => vec![],
+                }
+            }
+        };
+        patterns.into_iter().filter(|p: &Vec<_>| !p.is_empty()).collect()
+    }
+
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BingoBoard {
     numbers: Vec<Vec<(u32, bool)>>,
+    // number -> cells holding it
+    index: HashMap<u32, Vec<(usize, usize)>>,
+    // winning cell groups, and the patterns each cell belongs to
+    patterns: Vec<Vec<(usize, usize)>>,
+    cell_patterns: Vec<Vec<Vec<usize>>>,
+    // marked cells in each pattern
+    hits: Vec<usize>,
+    unmarked_sum: u64,
+    won: bool,
 }
 
 impl BingoBoard {
@@ -14,46 +92,79 @@ impl BingoBoard {
     }
 
     pub fn new(numbers: Vec<Vec<u32>>) -> BingoBoard {
+        assert!(!numbers.is_empty() && !numbers[0].is_empty(), "Board must not be empty");
         for n in &numbers {
-            assert_eq!(n.len(), 5);
+            assert_eq!(n.len(), numbers[0].len(), "Board rows must be the same length");
+        }
+        let mut index: HashMap<u32, Vec<(usize, usize)>> = HashMap::new();
+        for (r, row) in numbers.iter().enumerate() {
+            for (c, &n) in row.iter().enumerate() {
+                index.entry(n).or_default().push((r, c));
+            }
         }
-        assert_eq!(numbers.len(), 5);
         let numbers_bool = numbers.iter().map(|l| l.iter().map(|&n| (n, false)).collect()).collect();
-        BingoBoard { numbers: numbers_bool}
+        let board = BingoBoard {
+            numbers: numbers_bool,
+            index,
+            patterns: vec![],
+            cell_patterns: vec![],
+            hits: vec![],
+            unmarked_sum: numbers.iter().flatten().map(|&n| n as u64).sum(),
+            won: false,
+        };
+        board.with_rules(&WinRule::standard())
+    }
+
+    pub fn with_rules(mut self, rules: &[WinRule]) -> BingoBoard {
+        let (rows, cols) = self.size();
+        self.patterns = rules.iter().flat_map(|r| r.patterns(rows, cols))
+            .map(|mut p| { p.sort_unstable(); p.dedup(); p })
+            .collect();
+        self.cell_patterns = vec![vec![vec![]; cols]; rows];
+        for (i, p) in self.patterns.iter().enumerate() {
+            p.iter().for_each(|&(r, c)| self.cell_patterns[r][c].push(i));
+        }
+        // recount the hits for any cells already marked
+        self.hits = self.patterns.iter()
+            .map(|p| p.iter().filter(|&&(r, c)| self.numbers[r][c].1).count())
+            .collect();
+        self.won = self.hits.iter().zip(&self.patterns).any(|(&h, p)| h == p.len());
+        self
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.numbers.len(), self.numbers[0].len())
     }
 
     pub fn mark(&mut self, number: u32) -> bool {
-        let found = self.numbers.iter_mut()
-            .flatten()
-            .find(|n| n.0==number);
-        if found.is_some() {
-            found.unwrap().1 = true;
+        if let Some(cells) = self.index.get(&number) {
+            for &(r, c) in cells {
+                let cell = &mut self.numbers[r][c];
+                if cell.1 {
+                    continue; // already marked
+                }
+                cell.1 = true;
+                self.unmarked_sum -= cell.0 as u64;
+                for &p in &self.cell_patterns[r][c] {
+                    self.hits[p] += 1;
+                    if self.hits[p] == self.patterns[p].len() {
+                        self.won = true;
+                    }
+                }
+            }
         }
-        self.is_winner()
+        self.won
     }
 
     pub fn is_winner(&self) -> bool {
-        // check rows
-        self.numbers.iter()
-            .map(|r| r.iter().all(|n| n.1))
-            .any(|m| m)
-        ||
-        // check cols
-        (0..self.numbers.len())
-            .map(|c| self.numbers.iter().map(|r| r[c]).all(|n| n.1))
-            .any(|m| m)
-    }
-
-    pub fn score(&self, number: u32) -> Option<u32> {
+        self.won
+    }
+
+    pub fn score(&self, number: u32) -> Option<u64> {
         if !self.is_winner() {
             return None;
         }
-        let sum: u32 = self.numbers.iter()
-            .flatten()
-            .filter(|&n| !n.1)
-            .map(|n| n.0)
-            .sum();
-        Some(sum * number)
+        Some(self.unmarked_sum * number as u64)
     }
 }
 
@@ -77,9 +188,121 @@ impl fmt::Display for BingoBoard {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Win {
+    pub draw: usize,
+    pub number: u32,
+    pub score: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameResult {
+    // one entry per board, None if the board never won
+    pub wins: Vec<Option<Win>>,
+    // the state of every board after each draw, if requested
+    pub replay: Option<Vec<String>>,
+}
+
+impl GameResult {
+
+    // (board index, win) ordered by when the board won
+    pub fn ranking(&self) -> Vec<(usize, Win)> {
+        let mut ranking: Vec<_> = self.wins.iter().enumerate()
+            .filter_map(|(b, w)| w.map(|w| (b, w)))
+            .collect();
+        ranking.sort_by_key(|&(b, w)| (w.draw, b));
+        ranking
+    }
+
+    // one line per board, winners in the order they won then the others
+    pub fn ranking_report(&self) -> String {
+        let mut output: Vec<String> = self.ranking().iter().enumerate()
+            .map(|(i, (b, w))| format!("{}. Board {}: won on draw {} ({}), score {}", i + 1, b, w.draw, w.number, w.score))
+            .collect();
+        output.extend(self.wins.iter().enumerate().filter(|(_, w)| w.is_none())
+            .map(|(b, _)| format!("-. Board {}: never won", b)));
+        output.join("\n") + "\n"
+    }
+
+    pub fn first(&self) -> Option<(usize, Win)> {
+        self.ranking().first().copied()
+    }
+
+    pub fn last(&self) -> Option<(usize, Win)> {
+        self.ranking().last().copied()
+    }
+
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BingoGame {
+    draws: Vec<u32>,
+    boards: Vec<BingoBoard>,
+}
+
+impl BingoGame {
+
+    // Parse the draw line followed by blank line separated boards
+    pub fn parse(input: &str) -> BingoGame {
+        let mut groups = input.split("\n\n");
+        let draws = groups.next().unwrap().trim().split(',').map(|n| n.parse().unwrap()).collect();
+        let boards = groups.filter(|b| !b.trim().is_empty()).map(BingoBoard::parse).collect();
+        BingoGame::new(draws, boards)
+    }
+
+    pub fn new(draws: Vec<u32>, boards: Vec<BingoBoard>) -> BingoGame {
+        BingoGame { draws, boards }
+    }
+
+    pub fn with_rules(mut self, rules: &[WinRule]) -> BingoGame {
+        self.boards = self.boards.into_iter().map(|b| b.with_rules(rules)).collect();
+        self
+    }
+
+    pub fn boards(&self) -> &[BingoBoard] {
+        &self.boards
+    }
+
+    pub fn play(&self, replay: bool) -> GameResult {
+        let mut boards = self.boards.clone();
+        let mut wins = vec![None; boards.len()];
+        let mut frames = vec![];
+        // number -> boards holding it, so each draw only visits those boards
+        let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (i, b) in boards.iter().enumerate() {
+            b.index.keys().for_each(|&n| index.entry(n).or_default().push(i));
+        }
+        let mut remaining = boards.len();
+        for (draw, &n) in self.draws.iter().enumerate() {
+            if remaining == 0 && !replay {
+                break;
+            }
+            for &i in index.get(&n).map_or(&[][..], |v| v.as_slice()) {
+                if wins[i].is_some() {
+                    continue; // board has already won
+                }
+                let b = &mut boards[i];
+                if b.mark(n) {
+                    wins[i] = Some(Win { draw, number: n, score: b.score(n).unwrap() });
+                    remaining -= 1;
+                }
+            }
+            if replay {
+                let mut frame = format!("Draw {}: {}\n", draw, n);
+                for (i, b) in boards.iter().enumerate() {
+                    frame.push_str(&format!("Board {}\n{}\n", i, b));
+                }
+                frames.push(frame);
+            }
+        }
+        GameResult { wins, replay: if replay { Some(frames) } else { None } }
+    }
+
+}
+
 pub fn day4(args: &[String]) -> i32 {
     println!("Day 4");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -88,28 +311,33 @@ pub fn day4(args: &[String]) -> i32 {
     let contents = fs::read_to_string(filename)
         .expect("Something went wrong reading the file");
 
-    let mut groups = contents.split("\n\n");
-    let drawn: Vec<u32> = groups.next().unwrap().split(",").map(|n| n.parse().unwrap()).collect();
-    let mut boards: Vec<BingoBoard> = groups.map(|b| BingoBoard::parse(b)).collect();
-
-    let mut won = vec![false; boards.len()];
-    let mut scores = vec![];
-    for n in drawn {
-        // println!("Number Drawn: {}", n);
-        for (b, w) in &mut boards.iter_mut().zip(won.iter_mut()) {
-            if *w {
-                continue; // board has already won
+    let rules = match utils::option(args, "--rules") {
+        Some(r) => match WinRule::parse_list(r) {
+            Some(rules) => rules,
+            None => {
+                println!("Invalid rules: {}", r);
+                return -1;
             }
-            if b.mark(n) {
-                // println!("{}", b);
-                scores.push(b.score(n).unwrap());
-                *w = true;
-            }
-        }
+        },
+        None => WinRule::standard(),
+    };
+    let game = BingoGame::parse(&contents).with_rules(&rules);
+    let result = game.play(utils::flag(args, "--replay"));
+
+    if let Some(frames) = &result.replay {
+        frames.iter().for_each(|f| println!("{}", f));
+    }
+    if utils::flag(args, "--ranking") {
+        print!("{}", result.ranking_report());
+    }
+
+    match (result.first(), result.last()) {
+        (Some((_, first)), Some((_, last))) => {
+            println!("Part 1: {}", first.score);
+            println!("Part 2: {}", last.score);
+        },
+        _ => println!("No board won"),
     }
 
-    println!("Part 1: {}", scores.first().unwrap());  
-    println!("Part 2: {}", scores.last().unwrap());  
-    
     0
 }
@@ -1,6 +1,17 @@
 use std::fs;
 use std::fmt;
+use std::collections::{BTreeMap, HashMap};
 use regex::Regex;
+use super::utils;
+
+// A line through the lattice as (dx, dy, c): the smallest integer direction,
+// pointing right (or down when vertical), and the constant dx*y - dy*x
+// shared by every point on the line.
+type Family = (i128, i128, i128);
+
+// Line arithmetic multiplies up to three coordinates together, which stays
+// within i128 for coordinates up to here.
+pub const MAX_COORDINATE: usize = 1 << 40;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VentLine {
@@ -27,29 +38,78 @@ impl VentLine {
     }
 
     pub fn get_range(a: usize, b: usize) -> Vec<usize> {
-        assert_ne!(a, b);
-        match a < b {
+        match a <= b {
             true => (a..b+1).collect(),
             false => (b..a+1).rev().collect()
         }
     }
 
+    pub fn in_range(&self) -> bool {
+        [self.start.0, self.start.1, self.end.0, self.end.1].iter().all(|&c| c <= MAX_COORDINATE)
+    }
+
+    pub fn is_included(&self, diagonal: bool) -> bool {
+        let (dx, dy) = self.delta();
+        dx == 0 || dy == 0 || diagonal
+    }
+
+    fn delta(&self) -> (i128, i128) {
+        (self.end.0 as i128 - self.start.0 as i128, self.end.1 as i128 - self.start.1 as i128)
+    }
+
+    // The line this vent lies on, and the positions of its ends along it.
+    // A position is dx*x + dy*y, so neighbouring points are dx*dx + dy*dy apart.
+    fn family(&self) -> (Family, i128, i128) {
+        let (mut dx, mut dy) = self.delta();
+        let g = gcd(dx.abs(), dy.abs());
+        (dx, dy) = match g {
+            0 => (1, 0), // a single point
+            _ => (dx / g, dy / g),
+        };
+        if dx < 0 || (dx == 0 && dy < 0) {
+            (dx, dy) = (-dx, -dy);
+        }
+        let (x, y) = (self.start.0 as i128, self.start.1 as i128);
+        let (ex, ey) = (self.end.0 as i128, self.end.1 as i128);
+        let (t0, t1) = (dx * x + dy * y, dx * ex + dy * ey);
+        ((dx, dy, dx * y - dy * x), t0.min(t1), t0.max(t1))
+    }
+
+    // Every lattice point on the line. Lines which are neither horizontal nor
+    // vertical are only included when diagonal is set.
     pub fn get_points(&self, diagonal: bool) -> Vec<(usize, usize)> {
         if self.start.0 == self.end.0 {
-            return VentLine::get_range(self.start.1, self.end.1).iter()
-                .map(|&i| (self.start.0, i)).collect();
+            VentLine::get_range(self.start.1, self.end.1).iter()
+                .map(|&i| (self.start.0, i)).collect()
         } else if self.start.1 == self.end.1 {
-            return VentLine::get_range(self.start.0, self.end.0).iter()
-                .map(|&i| (i, self.start.1)).collect();
+            VentLine::get_range(self.start.0, self.end.0).iter()
+                .map(|&i| (i, self.start.1)).collect()
+        } else if diagonal {
+            let (dx, dy) = self.delta();
+            let g = gcd(dx.abs(), dy.abs());
+            let (x, y) = (self.start.0 as i128, self.start.1 as i128);
+            (0..=g).map(|k| ((x + k * dx / g) as usize, (y + k * dy / g) as usize)).collect()
         } else {
-            if diagonal {
-                let xr = VentLine::get_range(self.start.0, self.end.0);
-                let yr = VentLine::get_range(self.start.1, self.end.1);
-                return xr.into_iter().zip(yr.into_iter()).collect();
-            } else {
-                return vec![];
-            }
+            vec![]
+        }
+    }
+
+    // The points shared by two vents, worked out from the lines rather than
+    // by listing the points of each.
+    pub fn intersections(&self, other: &VentLine) -> Vec<(usize, usize)> {
+        assert!(self.in_range() && other.in_range(), "Coordinates above {} are not supported", MAX_COORDINATE);
+        let (f1, s1, e1) = self.family();
+        let (f2, s2, e2) = other.family();
+        let a = Piece { family: f1, start: s1, end: e1, count: 1 };
+        if f1 == f2 {
+            let step = f1.0 * f1.0 + f1.1 * f1.1;
+            return (s1.max(s2)..=e1.min(e2)).step_by(step as usize)
+                .map(|t| a.point(t))
+                .map(|(x, y)| (x as usize, y as usize))
+                .collect();
         }
+        let b = Piece { family: f2, start: s2, end: e2, count: 1 };
+        a.crossing(&b).map(|(x, y)| (x as usize, y as usize)).into_iter().collect()
     }
 
 }
@@ -77,13 +137,44 @@ impl VentMap {
         self.counts.iter().flatten().filter(|&v| *v >= min_count).count()
     }
 
-}
+    pub fn size(&self) -> (usize, usize) {
+        (self.counts[0].len(), self.counts.len())
+    }
 
-impl fmt::Display for VentMap {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    pub fn max_count(&self) -> u32 {
+        self.counts.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    // Plain (P2) greyscale netpbm image of the counts
+    pub fn to_pgm(&self) -> String {
+        let (xsize, ysize) = self.size();
+        let max = self.max_count().max(1);
+        let header = format!("P2\n{} {}\n{}\n", xsize, ysize, max);
+        utils::netpbm(header, self.counts.iter().flatten().map(|c| c.to_string()))
+    }
+
+    // Plain (P3) colour netpbm image of the counts
+    pub fn to_ppm(&self, scale: &ColourScale) -> String {
+        let (xsize, ysize) = self.size();
+        let max = self.max_count();
+        let header = format!("P3\n{} {}\n255\n", xsize, ysize);
+        utils::netpbm(header, self.counts.iter().flatten()
+            .map(|&c| scale.colour(c, max))
+            .flat_map(|(r, g, b)| [r, g, b])
+            .map(|v| v.to_string()))
+    }
+
+    // The map between two opposite corners (inclusive, in either order),
+    // clipped to the map size
+    pub fn crop(&self, corner: (usize, usize), opposite: (usize, usize)) -> String {
+        let (xsize, ysize) = self.size();
+        let (x0, x1) = (corner.0.min(opposite.0), corner.0.max(opposite.0));
+        let (y0, y1) = (corner.1.min(opposite.1), corner.1.max(opposite.1));
+        let xr = x0.min(xsize)..(x1 + 1).min(xsize);
+        let yr = y0.min(ysize)..(y1 + 1).min(ysize);
         let mut output = Vec::new();
-        for r in &self.counts {
-            for c in r {
+        for r in &self.counts[yr] {
+            for c in &r[xr.clone()] {
                 if *c == 0 {
                     output.push(String::from("."));
                 } else {
@@ -92,14 +183,263 @@ impl fmt::Display for VentMap {
             }
             output.push(String::from("\n"));
         }
-        write!(f, "{}", output.join(""))
+        output.join("")
+    }
+
+}
+
+// Colours spread evenly from zero to the highest count
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColourScale {
+    stops: Vec<(u8, u8, u8)>,
+}
+
+impl ColourScale {
+
+    pub fn new(stops: Vec<(u8, u8, u8)>) -> ColourScale {
+        assert!(!stops.is_empty(), "A colour scale needs at least one colour");
+        ColourScale { stops }
+    }
+
+    pub fn grey() -> ColourScale {
+        ColourScale::new(vec![(0, 0, 0), (255, 255, 255)])
+    }
+
+    pub fn heat() -> ColourScale {
+        ColourScale::new(vec![(0, 0, 0), (128, 0, 0), (255, 64, 0), (255, 255, 0), (255, 255, 255)])
+    }
+
+    // Parse a scale name or a list of hex colours (ex: "heat" or "000000,ff0000,ffff00")
+    pub fn parse(scale: &str) -> Option<ColourScale> {
+        match scale {
+            "grey" => Some(ColourScale::grey()),
+            "heat" => Some(ColourScale::heat()),
+            _ => {
+                let stops = scale.split(',')
+                    .map(|c| {
+                        let c = c.trim_start_matches('#');
+                        if c.len() != 6 {
+                            return None;
+                        }
+                        let v = u32::from_str_radix(c, 16).ok()?;
+                        Some(((v >> 16) as u8, (v >> 8) as u8, v as u8))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some(ColourScale::new(stops))
+            }
+        }
+    }
+
+    pub fn colour(&self, value: u32, max: u32) -> (u8, u8, u8) {
+        if self.stops.len() == 1 || max == 0 {
+            return self.stops[0];
+        }
+        // position along the scale, in units of the gap between stops
+        let pos = value.min(max) as f64 / max as f64 * (self.stops.len() - 1) as f64;
+        let i = (pos.floor() as usize).min(self.stops.len() - 2);
+        let frac = pos - i as f64;
+        let (a, b) = (self.stops[i], self.stops[i + 1]);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * frac).round() as u8;
+        (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+    }
+
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+// A stretch of a line covered by the same number of vents
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Piece {
+    family: Family,
+    start: i128,
+    end: i128,
+    count: u32,
+}
+
+impl Piece {
+
+    fn len(&self) -> usize {
+        let (dx, dy, _) = self.family;
+        ((self.end - self.start) / (dx * dx + dy * dy)) as usize + 1
+    }
+
+    // the point at position t along the line
+    fn point(&self, t: i128) -> (i128, i128) {
+        let (dx, dy, c) = self.family;
+        let step = dx * dx + dy * dy;
+        ((dx * t - dy * c) / step, (dy * t + dx * c) / step)
+    }
+
+    fn contains(&self, (x, y): (i128, i128)) -> bool {
+        let (dx, dy, _) = self.family;
+        (self.start..=self.end).contains(&(dx * x + dy * y))
+    }
+
+    // the lattice point where two pieces on different lines cross
+    fn crossing(&self, other: &Piece) -> Option<(i128, i128)> {
+        let (dx1, dy1, c1) = self.family;
+        let (dx2, dy2, c2) = other.family;
+        let det = dx1 * dy2 - dy1 * dx2;
+        if det == 0 {
+            return None;
+        }
+        let (xn, yn) = (c1 * dx2 - dx1 * c2, c1 * dy2 - dy1 * c2);
+        if xn % det != 0 || yn % det != 0 {
+            return None;
+        }
+        let p = (xn / det, yn / det);
+        Some(p).filter(|&p| self.contains(p) && other.contains(p))
+    }
+
+}
+
+// Overlap counter which only stores the covered stretches of each line and
+// the points where lines cross, so its size depends on the number of lines
+// and crossings rather than the size of the map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseVentMap {
+    pieces: Vec<Piece>,
+    // crossing point -> the pieces passing through it
+    crossings: HashMap<(i128, i128), Vec<usize>>,
+}
+
+impl SparseVentMap {
+
+    pub fn new(ventlines: &[VentLine], diagonal: bool) -> SparseVentMap {
+        assert!(ventlines.iter().all(|v| v.in_range()), "Coordinates above {} are not supported", MAX_COORDINATE);
+        let mut families: HashMap<Family, Vec<(i128, i128)>> = HashMap::new();
+        ventlines.iter()
+            .filter(|v| v.is_included(diagonal))
+            .map(|v| v.family())
+            .for_each(|(f, t0, t1)| families.entry(f).or_default().push((t0, t1)));
+
+        let mut pieces = vec![];
+        for (family, spans) in families {
+            // sweep along the line counting how many vents cover each stretch
+            let step = family.0 * family.0 + family.1 * family.1;
+            let mut events: Vec<(i128, i32)> = spans.iter()
+                .flat_map(|&(t0, t1)| [(t0, 1), (t1 + step, -1)])
+                .collect();
+            events.sort_unstable();
+            let mut count = 0;
+            for (i, &(t, delta)) in events.iter().enumerate() {
+                count += delta;
+                match events.get(i + 1) {
+                    Some(&(next, _)) if count > 0 && next > t => pieces.push(
+                        Piece { family, start: t, end: next - step, count: count as u32 }),
+                    _ => (),
+                }
+            }
+        }
+
+        let crossings = SparseVentMap::crossings(&pieces);
+        SparseVentMap { pieces, crossings }
+    }
+
+    // Crossings between the pieces of each pair of directions u and v.
+    // Measuring a point P by (u×P, v×P) turns the u pieces into vertical
+    // segments and the v pieces into horizontal ones, so sweeping across the
+    // first measure pairs up only the pieces which meet, each pair once. For
+    // n pieces in d directions with k crossings that is O(d·n·log n + k).
+    fn crossings(pieces: &[Piece]) -> HashMap<(i128, i128), Vec<usize>> {
+        let mut directions: HashMap<(i128, i128), Vec<usize>> = HashMap::new();
+        for (i, p) in pieces.iter().enumerate() {
+            directions.entry((p.family.0, p.family.1)).or_default().push(i);
+        }
+        let directions: Vec<Vec<usize>> = directions.into_values().collect();
+
+        let mut crossings: HashMap<(i128, i128), Vec<usize>> = HashMap::new();
+        for (a, us) in directions.iter().enumerate() {
+            for vs in &directions[a + 1..] {
+                for (i, j) in SparseVentMap::meeting(pieces, us, vs) {
+                    if let Some(p) = pieces[i].crossing(&pieces[j]) {
+                        crossings.entry(p).or_default().extend([i, j]);
+                    }
+                }
+            }
+        }
+        crossings.values_mut().for_each(|c| { c.sort_unstable(); c.dedup(); });
+        crossings
+    }
+
+    // pairs of a piece from `us` and one from `vs` which touch, the pieces of
+    // each sharing a direction
+    fn meeting(pieces: &[Piece], us: &[usize], vs: &[usize]) -> Vec<(usize, usize)> {
+        let direction = |i: usize| (pieces[i].family.0, pieces[i].family.1);
+        let (u, v) = (direction(us[0]), direction(vs[0]));
+        let cross = |d: (i128, i128), (x, y): (i128, i128)| d.0 * y - d.1 * x;
+        let measures = |i: usize| {
+            let (a, b) = (pieces[i].point(pieces[i].start), pieces[i].point(pieces[i].end));
+            ((cross(u, a), cross(u, b)), (cross(v, a), cross(v, b)))
+        };
+
+        // (first measure, kind, piece, second measure range): the v pieces
+        // open (0) before the u pieces at the same place look for them (1),
+        // and close (2) after
+        let mut events = vec![];
+        for &j in vs {
+            let ((x0, x1), (y, _)) = measures(j);
+            events.push((x0.min(x1), 0, j, y, y));
+            events.push((x0.max(x1), 2, j, y, y));
+        }
+        for &i in us {
+            let ((x, _), (y0, y1)) = measures(i);
+            events.push((x, 1, i, y0.min(y1), y0.max(y1)));
+        }
+        events.sort_unstable();
+
+        let mut open: BTreeMap<i128, Vec<usize>> = BTreeMap::new();
+        let mut pairs = vec![];
+        for (_, kind, k, y0, y1) in events {
+            match kind {
+                0 => open.entry(y0).or_default().push(k),
+                1 => open.range(y0..=y1).for_each(|(_, js)| pairs.extend(js.iter().map(|&j| (k, j)))),
+                _ => {
+                    let js = open.get_mut(&y0).unwrap();
+                    js.retain(|&j| j != k);
+                    if js.is_empty() {
+                        open.remove(&y0);
+                    }
+                },
+            }
+        }
+        pairs
+    }
+
+    pub fn overlapping_count(&self, min_count: u32) -> usize {
+        assert!(min_count > 0, "Only covered points are stored");
+        let mut total: usize = self.pieces.iter()
+            .filter(|p| p.count >= min_count)
+            .map(|p| p.len())
+            .sum();
+        // crossing points were counted once per qualifying piece, replace that with their real count
+        for pieces in self.crossings.values() {
+            total -= pieces.iter().filter(|&&i| self.pieces[i].count >= min_count).count();
+            if pieces.iter().map(|&i| self.pieces[i].count).sum::<u32>() >= min_count {
+                total += 1;
+            }
+        }
+        total
+    }
+
+}
+
+impl fmt::Display for VentMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (xsize, ysize) = self.size();
+        write!(f, "{}", self.crop((0, 0), (xsize, ysize)))
     }
 }
 
 
 pub fn day5(args: &[String]) -> i32 {
     println!("Day 5");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -110,11 +450,58 @@ pub fn day5(args: &[String]) -> i32 {
 
     let ventlines: Vec<_> = contents.lines().map(|l| VentLine::parse(l)).collect();
 
-    let ventmap = VentMap::new(&ventlines, false);    
-    println!("Part 1: {}", ventmap.overlapping_count(2)); 
+    // the dense map is quicker for small areas, the sparse one scales with the lines
+    let area = ventlines.iter().map(|v| v.start.0.max(v.end.0) + 1).max().unwrap_or(0)
+        .saturating_mul(ventlines.iter().map(|v| v.start.1.max(v.end.1) + 1).max().unwrap_or(0));
+    let sparse = utils::flag(args, "--sparse") || (area > 1 << 24 && !utils::flag(args, "--dense"));
+    if sparse && !ventlines.iter().all(|v| v.in_range()) {
+        println!("Coordinates above {} are not supported", MAX_COORDINATE);
+        return -1;
+    }
 
-    let ventmap2 = VentMap::new(&ventlines, true);
-    println!("Part 2: {}", ventmap2.overlapping_count(2));  
+    for (part, diagonal) in [(1, false), (2, true)] {
+        let count = match sparse {
+            true => SparseVentMap::new(&ventlines, diagonal).overlapping_count(2),
+            false => VentMap::new(&ventlines, diagonal).overlapping_count(2),
+        };
+        println!("Part {}: {}", part, count);
+    }
+
+    let (pgm, ppm, crop) = (utils::option(args, "--pgm"), utils::option(args, "--ppm"), utils::option(args, "--crop"));
+    if pgm.is_none() && ppm.is_none() && crop.is_none() {
+        return 0;
+    }
+    if sparse {
+        println!("Image export needs the dense map");
+        return -1;
+    }
+    let ventmap = VentMap::new(&ventlines, true);
+    if let Some(filename) = pgm {
+        fs::write(filename, ventmap.to_pgm()).expect("Unable to write image");
+        println!("Wrote {}", filename);
+    }
+    if let Some(filename) = ppm {
+        let scale = utils::option(args, "--scale").unwrap_or("heat");
+        match ColourScale::parse(scale) {
+            Some(scale) => {
+                fs::write(filename, ventmap.to_ppm(&scale)).expect("Unable to write image");
+                println!("Wrote {}", filename);
+            },
+            None => {
+                println!("Invalid colour scale: {}", scale);
+                return -1;
+            }
+        }
+    }
+    if let Some(region) = crop {
+        // region as "x0,y0,x1,y1"
+        let v: Vec<usize> = region.split(',').filter_map(|n| n.parse().ok()).collect();
+        if v.len() != 4 {
+            println!("Invalid region: {}", region);
+            return -1;
+        }
+        print!("{}", ventmap.crop((v[0], v[1]), (v[2], v[3])));
+    }
     
     0
 }
@@ -1,5 +1,6 @@
 use std::fs;
 use std::fmt;
+use super::utils;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Lanternfish {
@@ -29,13 +30,306 @@ impl fmt::Display for Lanternfish {
     }
 }
 
-pub fn lantern_count(timer: u32, days: u32) -> usize {
-    (timer..days).step_by(7).map(|t| lantern_count(9, days - t)).sum::<usize>() + 1
+// Fish grouped by timer value. A fish at 0 resets to `reset` and spawns a
+// new fish at `newborn`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Population {
+    buckets: Vec<usize>,
+    reset: usize,
+    newborn: usize,
+}
+
+impl Population {
+
+    pub fn new(reset: usize, newborn: usize) -> Population {
+        Population { buckets: vec![0; reset.max(newborn) + 1], reset, newborn }
+    }
+
+    pub fn lanternfish() -> Population {
+        Population::new(6, 8)
+    }
+
+    pub fn with_timers(mut self, timers: &[usize]) -> Population {
+        timers.iter().for_each(|&t| self.add(t, 1));
+        self
+    }
+
+    pub fn add(&mut self, timer: usize, count: usize) {
+        if timer >= self.buckets.len() {
+            self.buckets.resize(timer + 1, 0);
+        }
+        self.buckets[timer] += count;
+    }
+
+    // fish count for each timer value
+    pub fn buckets(&self) -> &[usize] {
+        &self.buckets
+    }
+
+    // The population a day later, or None when a count no longer fits in a usize
+    pub fn next_day(&self) -> Option<Population> {
+        let spawning = self.buckets[0];
+        let mut buckets = self.buckets[1..].to_vec();
+        buckets.push(0);
+        buckets[self.reset] = buckets[self.reset].checked_add(spawning)?;
+        buckets[self.newborn] = buckets[self.newborn].checked_add(spawning)?;
+        Some(Population { buckets, reset: self.reset, newborn: self.newborn })
+    }
+
+    pub fn count(&self) -> Option<usize> {
+        self.buckets.iter().try_fold(0usize, |total, &b| total.checked_add(b))
+    }
+
+    // None when the count doesn't fit in a usize, see count_after() for those
+    pub fn count_on(&self, day: usize) -> Option<usize> {
+        let mut p = self.clone();
+        for _ in 0..day {
+            p = p.next_day()?;
+        }
+        p.count()
+    }
+
+    // The population on each day from today up to and including `days`
+    pub fn history(&self, days: usize) -> Option<Vec<Population>> {
+        let mut history = vec![self.clone()];
+        for _ in 0..days {
+            let next = history.last().unwrap().next_day()?;
+            history.push(next);
+        }
+        Some(history)
+    }
+
+    // CSV of the history with a column per timer value, the total and the
+    // growth rate from the previous day, counted in the arithmetic of `unit`.
+    // The growth is left empty when the counts are only known modulo.
+    pub fn history_csv<T: Count + fmt::Display>(&self, days: usize, unit: &T) -> String {
+        let t = self.transition(unit);
+        let mut buckets: Vec<T> = self.buckets.iter().map(|&b| unit.of(b)).collect();
+        let mut output = vec![format!("day,{},total,growth",
+            (0..buckets.len()).map(|t| format!("t{}", t)).collect::<Vec<_>>().join(","))];
+        let mut previous: Option<f64> = None;
+        for day in 0..=days {
+            if day > 0 {
+                buckets = mat_vec(&t, &buckets, unit);
+            }
+            let total = buckets.iter().fold(unit.of(0), |acc, b| acc.add(b));
+            let growth = match (previous, total.approx()) {
+                (Some(p), Some(n)) if p > 0.0 => format!("{:.6}", n / p),
+                _ => String::new(),
+            };
+            previous = total.approx();
+            output.push(format!("{},{},{},{}", day,
+                buckets.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(","), total, growth));
+        }
+        output.join("\n") + "\n"
+    }
+
+    // The transition matrix T where T * buckets gives the next day's buckets
+    pub fn transition<T: Count>(&self, unit: &T) -> Vec<Vec<T>> {
+        let n = self.buckets.len();
+        let mut t = vec![vec![unit.of(0); n]; n];
+        for (i, row) in t.iter_mut().enumerate().take(n - 1) {
+            row[i + 1] = unit.of(1);
+        }
+        t[self.reset][0] = t[self.reset][0].add(&unit.of(1));
+        t[self.newborn][0] = t[self.newborn][0].add(&unit.of(1));
+        t
+    }
+
+    // Count after any number of days by raising the transition matrix to
+    // that power, in the arithmetic of `unit` (ex: BigUint or Modular)
+    pub fn count_after<T: Count>(&self, days: u64, unit: &T) -> T {
+        let mut m = self.transition(unit);
+        let mut v: Vec<T> = self.buckets.iter().map(|&b| unit.of(b)).collect();
+        let mut d = days;
+        while d > 0 {
+            if d & 1 == 1 {
+                v = mat_vec(&m, &v, unit);
+            }
+            d >>= 1;
+            if d > 0 {
+                m = mat_mul(&m, &m, unit);
+            }
+        }
+        v.iter().fold(unit.of(0), |acc, c| acc.add(c))
+    }
+
+}
+
+fn mat_mul<T: Count>(a: &[Vec<T>], b: &[Vec<T>], unit: &T) -> Vec<Vec<T>> {
+    let n = a.len();
+    (0..n).map(|i| (0..n).map(|j|
+        (0..n).fold(unit.of(0), |acc, k| acc.add(&a[i][k].mul(&b[k][j])))
+    ).collect()).collect()
+}
+
+fn mat_vec<T: Count>(a: &[Vec<T>], v: &[T], unit: &T) -> Vec<T> {
+    a.iter().map(|row|
+        row.iter().zip(v).fold(unit.of(0), |acc, (x, y)| acc.add(&x.mul(y)))
+    ).collect()
+}
+
+// Arithmetic used for counting with the transition matrix
+pub trait Count: Clone {
+    // n in the same arithmetic as self
+    fn of(&self, n: usize) -> Self;
+    fn add(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+    // the value as a float, when it is known exactly rather than modulo
+    fn approx(&self) -> Option<f64>;
+}
+
+// Unsigned integer of any size, stored as base 2^32 limbs with the least
+// significant first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+
+    pub fn new(n: u64) -> BigUint {
+        BigUint { limbs: vec![n as u32, (n >> 32) as u32] }.normalized()
+    }
+
+    fn normalized(mut self) -> BigUint {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        self
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    // divide in place by a small divisor, returning the remainder
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut rem = 0u64;
+        for l in self.limbs.iter_mut().rev() {
+            let cur = (rem << 32) | *l as u64;
+            *l = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        rem as u32
+    }
+
+}
+
+impl Count for BigUint {
+
+    fn of(&self, n: usize) -> BigUint {
+        BigUint::new(n as u64)
+    }
+
+    fn add(&self, other: &BigUint) -> BigUint {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = 0u64;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = *self.limbs.get(i).unwrap_or(&0) as u64 + *other.limbs.get(i).unwrap_or(&0) as u64 + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        BigUint { limbs }.normalized()
+    }
+
+    fn mul(&self, other: &BigUint) -> BigUint {
+        if self.is_zero() || other.is_zero() {
+            return BigUint { limbs: vec![] };
+        }
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let cur = limbs[i + j] as u64 + a as u64 * b as u64 + carry;
+                limbs[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        BigUint { limbs }.normalized()
+    }
+
+    fn approx(&self) -> Option<f64> {
+        Some(self.limbs.iter().rev().fold(0.0, |acc, &l| acc * 4294967296.0 + l as f64))
+    }
+
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // peel off 9 decimal digits at a time
+        let mut n = self.clone();
+        let mut chunks = vec![];
+        while !n.is_zero() {
+            chunks.push(n.div_rem_small(1_000_000_000));
+        }
+        let mut output = chunks.pop().unwrap().to_string();
+        chunks.iter().rev().for_each(|c| output.push_str(&format!("{:09}", c)));
+        write!(f, "{}", output)
+    }
+}
+
+// Counting modulo a fixed modulus
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Modular {
+    pub value: u64,
+    pub modulus: u64,
+}
+
+impl Modular {
+
+    pub fn new(value: u64, modulus: u64) -> Modular {
+        assert!(modulus > 0, "Modulus must be positive");
+        Modular { value: value % modulus, modulus }
+    }
+
+}
+
+impl Count for Modular {
+
+    fn of(&self, n: usize) -> Modular {
+        Modular::new(n as u64, self.modulus)
+    }
+
+    fn add(&self, other: &Modular) -> Modular {
+        Modular::new(((self.value as u128 + other.value as u128) % self.modulus as u128) as u64, self.modulus)
+    }
+
+    fn mul(&self, other: &Modular) -> Modular {
+        Modular::new(((self.value as u128 * other.value as u128) % self.modulus as u128) as u64, self.modulus)
+    }
+
+    fn approx(&self) -> Option<f64> {
+        None
+    }
+
+}
+
+impl fmt::Display for Modular {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+// Exact counts grow by about one digit every 27 days, and past this many days
+// working them out takes more than a few seconds
+const MAX_EXACT_DAYS: u64 = 1_000_000;
+
+pub fn lantern_count(timer: u32, days: u32) -> Option<usize> {
+    Population::lanternfish().with_timers(&[timer as usize]).count_on(days as usize)
 }
 
 pub fn day6(args: &[String]) -> i32 {
     println!("Day 6");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -44,32 +338,42 @@ pub fn day6(args: &[String]) -> i32 {
     let contents = fs::read_to_string(filename)
         .expect("Something went wrong reading the file");
 
-    let days1 = 80;
+    let timers: Vec<usize> = contents.trim().split(',').map(|l| l.parse().unwrap()).collect();
+    let reset = utils::option(args, "--reset").map_or(6, |r| r.parse().unwrap());
+    let newborn = utils::option(args, "--newborn").map_or(8, |n| n.parse().unwrap());
+    let population = Population::new(reset, newborn).with_timers(&timers);
 
-    let mut fish: Vec<_> = contents.split(",").map(|l| Lanternfish::new(l.parse().unwrap())).collect(); 
-
-    for _ in 0..days1 {
-        for j in 0..fish.len() {
-            let n = fish[j].next_day();
-            if n.is_some() {
-                fish.push(n.unwrap());
-            }
+    for (part, days) in [(1, 80), (2, 256)] {
+        match population.count_on(days) {
+            Some(count) => println!("Part {}: {}", part, count),
+            None => println!("Part {}: {}", part, population.count_after(days as u64, &BigUint::new(0))),
         }
     }
-    println!("Part 1: {}", fish.len());
-
-    let timers: Vec<u32> = contents.split(",").map(|l| l.parse().unwrap()).collect();
-    println!("Part 1: {}", timers.iter().map(|&t| lantern_count(t, days1)).sum::<usize>());
-    
-    let days2 = 256;
-
-    let mut timer_counts: Vec<usize> = vec![0; 6];
-    for t in 1..6 {
-        timer_counts[t] = lantern_count(t as u32, days2);
+    if let Some(days) = utils::option(args, "--history") {
+        let days = days.parse().unwrap();
+        let csv = match utils::option(args, "--modulus") {
+            Some(m) => population.history_csv(days, &Modular::new(0, m.parse().unwrap())),
+            None => population.history_csv(days, &BigUint::new(0)),
+        };
+        match utils::option(args, "--output") {
+            Some(filename) => {
+                fs::write(filename, csv).expect("Unable to write history");
+                println!("Wrote {}", filename);
+            },
+            None => print!("{}", csv),
+        }
+    }
+    if let Some(days) = utils::option(args, "--days") {
+        let days: u64 = days.parse().unwrap();
+        match utils::option(args, "--modulus") {
+            Some(m) => println!("Day {}: {} (mod {})", days, population.count_after(days, &Modular::new(0, m.parse().unwrap())), m),
+            None if days > MAX_EXACT_DAYS => {
+                println!("Exact counts are limited to {} days, add --modulus to count further", MAX_EXACT_DAYS);
+                return -1;
+            },
+            None => println!("Day {}: {}", days, population.count_after(days, &BigUint::new(0))),
+        }
     }
-    println!("Part 2: {}", timers.iter().map(|&t| timer_counts[t as usize]).sum::<usize>());
-       
+
     0
 }
-
-
@@ -1,22 +1,186 @@
 use std::fs;
+use super::utils;
 
 pub fn delta(a: usize, b: usize) -> usize {
-    (a as isize - b as isize).abs() as usize
+    a.abs_diff(b)
 }
 
-pub fn cost1(crab: &Vec<usize>, position: usize) -> usize {
-    crab.iter().map(|&c| delta(c, position)).sum()
+// Fuel burnt by one crab moving `distance` steps
+pub trait FuelCost {
+    fn cost(&self, distance: usize) -> usize;
+
+    // a range of positions known to hold the cheapest one, if the model has
+    // a shortcut for finding it
+    fn bounds(&self, _crabs: &[usize]) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+// One unit of fuel per step, cheapest at the median
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Linear;
+
+impl FuelCost for Linear {
+    fn cost(&self, distance: usize) -> usize {
+        distance
+    }
+
+    fn bounds(&self, crabs: &[usize]) -> Option<(usize, usize)> {
+        let mut sorted = crabs.to_vec();
+        sorted.sort_unstable();
+        Some((sorted[(sorted.len() - 1) / 2], sorted[sorted.len() / 2]))
+    }
+}
+
+// Each step costs one more than the last, cheapest within half a step of the mean
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: usize) -> usize {
+        distance * (distance + 1) / 2
+    }
+
+    fn bounds(&self, crabs: &[usize]) -> Option<(usize, usize)> {
+        let sum: usize = crabs.iter().sum();
+        let n = crabs.len();
+        Some(((sum / n).saturating_sub(1), sum.div_ceil(n) + 1))
+    }
+}
+
+// any closure can be used as a cost model
+impl<F: Fn(usize) -> usize> FuelCost for F {
+    fn cost(&self, distance: usize) -> usize {
+        self(distance)
+    }
 }
 
-pub fn cost2(crab: &Vec<usize>, position: usize) -> usize {
-    crab.iter()
-        .map(|&c| (0..delta(c, position) + 1).sum::<usize>())
-        .sum()
+pub fn total_cost(crabs: &[usize], position: usize, model: &impl FuelCost) -> usize {
+    crabs.iter().map(|&c| model.cost(delta(c, position))).sum()
+}
+
+pub fn cost1(crab: &[usize], position: usize) -> usize {
+    total_cost(crab, position, &Linear)
+}
+
+pub fn cost2(crab: &[usize], position: usize) -> usize {
+    total_cost(crab, position, &Triangular)
+}
+
+// The cheapest position and its cost. Models without bounds are searched
+// assuming the cost grows convexly with distance, which makes the total
+// convex in the position.
+pub fn align(crabs: &[usize], model: &impl FuelCost) -> (usize, usize) {
+    assert!(!crabs.is_empty(), "No crabs to align");
+    let (min, max) = (*crabs.iter().min().unwrap(), *crabs.iter().max().unwrap());
+    let (lo, hi) = match model.bounds(crabs) {
+        Some((lo, hi)) => (lo.max(min), hi.min(max)),
+        None => {
+            // binary search for the first position no cheaper than the next
+            let (mut lo, mut hi) = (min, max);
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if total_cost(crabs, mid, model) <= total_cost(crabs, mid + 1, model) {
+                    hi = mid;
+                } else {
+                    lo = mid + 1;
+                }
+            }
+            (lo, lo)
+        }
+    };
+    (lo..=hi).map(|p| (p, total_cost(crabs, p, model)))
+        .min_by_key(|&(p, c)| (c, p))
+        .unwrap()
+}
+
+// Total cost at every position between the outermost crabs
+pub fn cost_curve(crabs: &[usize], model: &impl FuelCost) -> Vec<(usize, usize)> {
+    let (min, max) = (*crabs.iter().min().unwrap(), *crabs.iter().max().unwrap());
+    (min..=max).map(|p| (p, total_cost(crabs, p, model))).collect()
+}
+
+// The positions costing at most `percent` more than the cheapest one
+pub fn within(curve: &[(usize, usize)], percent: f64) -> (usize, usize) {
+    let best = curve.iter().map(|&(_, c)| c).min().unwrap();
+    let limit = best as f64 * (1.0 + percent / 100.0);
+    let positions: Vec<usize> = curve.iter().filter(|&&(_, c)| c as f64 <= limit).map(|&(p, _)| p).collect();
+    (*positions.first().unwrap(), *positions.last().unwrap())
+}
+
+pub fn curve_csv(curve: &[(usize, usize)], percent: f64) -> String {
+    let best = curve.iter().map(|&(_, c)| c).min().unwrap();
+    let (lo, hi) = within(curve, percent);
+    let mut output = vec![String::from("position,cost,minimum,within")];
+    for &(p, c) in curve {
+        output.push(format!("{},{},{},{}", p, c, (c == best) as u8, (lo..=hi).contains(&p) as u8));
+    }
+    output.join("\n") + "\n"
+}
+
+// Plot of the curve squeezed into width x height characters. Columns holding
+// the minimum are drawn 'O' and those within `percent` of it '+'.
+pub fn curve_plot(curve: &[(usize, usize)], percent: f64, width: usize, height: usize) -> String {
+    let best = curve.iter().map(|&(_, c)| c).min().unwrap();
+    let worst = curve.iter().map(|&(_, c)| c).max().unwrap();
+    let (lo, hi) = within(curve, percent);
+    let width = width.min(curve.len()).max(1);
+    let height = height.max(2);
+
+    // cheapest cost and marker for each column
+    let mut columns: Vec<Option<(usize, char)>> = vec![None; width];
+    for (i, &(p, c)) in curve.iter().enumerate() {
+        let col = i * width / curve.len();
+        let mark = if c == best { 'O' } else if (lo..=hi).contains(&p) { '+' } else { '*' };
+        columns[col] = match columns[col] {
+            Some((cc, m)) if cc < c || (cc == c && m == 'O') => Some((cc, m)),
+            _ => Some((c, mark)),
+        };
+    }
+
+    let label_width = worst.to_string().len();
+    let mut output = vec![];
+    for row in 0..height {
+        let label = match row {
+            0 => worst.to_string(),
+            _ if row == height - 1 => best.to_string(),
+            _ => String::new(),
+        };
+        let line: String = columns.iter().map(|c| match c {
+            Some((c, m)) if worst == best || (worst - c) * (height - 1) / (worst - best) == row => *m,
+            _ => ' ',
+        }).collect();
+        output.push(format!("{:>w$} |{}", label, line.trim_end(), w = label_width));
+    }
+    output.push(format!("{:>w$} +{}", "", "-".repeat(width), w = label_width));
+    let (first, last) = (curve.first().unwrap().0.to_string(), curve.last().unwrap().0.to_string());
+    output.push(format!("{:>w$}  {}{:>r$}", "", first, last,
+        w = label_width, r = width.saturating_sub(first.len()).max(last.len() + 1)));
+    output.push(format!("Minimum {} at {:?}, within {}%: {}..={}", best,
+        curve.iter().filter(|&&(_, c)| c == best).map(|&(p, _)| p).collect::<Vec<_>>(), percent, lo, hi));
+    output.join("\n") + "\n"
+}
+
+// print or save the cost curve as requested on the command line
+fn report_curve(crabs: &[usize], model: &impl FuelCost, args: &[String]) {
+    let curve = cost_curve(crabs, model);
+    let percent = utils::option(args, "--within").map_or(5.0, |p| p.parse().unwrap());
+    let output = match utils::flag(args, "--csv") {
+        true => curve_csv(&curve, percent),
+        false => curve_plot(&curve, percent, 78, 20),
+    };
+    match utils::option(args, "--output") {
+        Some(filename) => {
+            fs::write(filename, output).expect("Unable to write curve");
+            println!("Wrote {}", filename);
+        },
+        None => print!("{}", output),
+    }
 }
 
 pub fn day7(args: &[String]) -> i32 {
     println!("Day 7");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -25,12 +189,20 @@ pub fn day7(args: &[String]) -> i32 {
     let contents = fs::read_to_string(filename)
         .expect("Something went wrong reading the file");
 
-    let crab: Vec<_> = contents.split(",").map(|l| l.parse().unwrap()).collect(); 
+    let crab: Vec<usize> = contents.trim().split(',').map(|l| l.parse().unwrap()).collect();
+
+    println!("Part 1: {}", align(&crab, &Linear).1);
+    println!("Part 2: {}", align(&crab, &Triangular).1);
 
-    println!("Part 1: {}", (0..crab.len()).map(|p| cost1(&crab, p)).min().unwrap());
-    println!("Part 2: {}", (0..crab.len()).map(|p| cost2(&crab, p)).min().unwrap());
+    match utils::option(args, "--curve") {
+        Some("linear") => report_curve(&crab, &Linear, args),
+        Some("triangular") => report_curve(&crab, &Triangular, args),
+        Some(model) => {
+            println!("Unknown cost model: {}", model);
+            return -1;
+        },
+        None => (),
+    }
 
     0
 }
-
-
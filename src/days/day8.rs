@@ -1,11 +1,18 @@
 use std::fs;
-use regex::Regex;
+use std::fmt;
+use super::utils;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SevenSegment {
     segments: Vec<char>,
 }
 
+impl fmt::Display for SevenSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.segments.iter().collect::<String>())
+    }
+}
+
 impl SevenSegment {
     pub fn new(letters: &str) -> SevenSegment {
         SevenSegment { segments: letters.chars().collect() }
@@ -20,101 +27,262 @@ impl SevenSegment {
     }
 
     pub fn get_number(segment: SevenSegment) -> Option<usize> {
-        let mut l = segment.segments.clone();
-        l.sort();
-        match l.iter().collect::<String>().as_str() {
-            "abcefg" => Some(0),
-            "cf" => Some(1),
-            "acdeg" => Some(2),
-            "acdfg" => Some(3),
-            "bcdf" => Some(4),
-            "abdfg" => Some(5),
-            "abdefg" => Some(6),
-            "acf" => Some(7),
-            "abcdefg" => Some(8),
-            "abcdfg" => Some(9),
-            _ => None
-        }
+        DisplayTable::seven_segment().lookup(&segment)
+            .and_then(|d| d.to_digit(10)).map(|d| d as usize)
     }
 
 }
 
+// 14 segment glyphs: a-f run clockwise round the edge from the top, g and h
+// are the left and right halves of the middle bar, i j k the upper diagonal,
+// vertical and diagonal strokes and l m n the lower ones
+const FOURTEEN_SEGMENT: [(char, &str); 36] = [
+    ('0', "abcdefkl"), ('1', "bc"), ('2', "abdegh"), ('3', "abcdh"), ('4', "bcfgh"),
+    ('5', "acdfgh"), ('6', "acdefgh"), ('7', "abc"), ('8', "abcdefgh"), ('9', "abcdfgh"),
+    ('A', "abcefgh"), ('B', "abcdhjm"), ('C', "adef"), ('D', "abcdjm"), ('E', "adefg"),
+    ('F', "aefg"), ('G', "acdefh"), ('H', "bcefgh"), ('I', "adjm"), ('J', "bcde"),
+    ('K', "efgkn"), ('L', "def"), ('M', "bcefik"), ('N', "bcefin"), ('O', "abcdef"),
+    ('P', "abefgh"), ('Q', "abcdefn"), ('R', "abefghn"), ('S', "acdhi"), ('T', "ajm"),
+    ('U', "bcdef"), ('V', "efkl"), ('W', "bcefln"), ('X', "ikln"), ('Y', "ikm"),
+    ('Z', "adkl"),
+];
+
+// The 16 segment layout splits the top and bottom bars of the 14 segment one.
+// Most glyphs light both halves, the overrides tell the halves apart.
+const SIXTEEN_OVERRIDES: [(char, &str); 2] = [('1', "aefms"), ('J', "cdes")];
+const SIXTEEN_FROM_FOURTEEN: [(char, &str); 14] = [
+    ('a', "ab"), ('b', "c"), ('c', "d"), ('d', "ef"), ('e', "g"), ('f', "h"), ('g', "u"),
+    ('h', "p"), ('i', "k"), ('j', "m"), ('k', "n"), ('l', "t"), ('m', "s"), ('n', "r"),
+];
+
+// A display definition: the segment names, the segments lit for each glyph
+// and, for drawing, an art template where each segment's name marks where its
+// stroke character goes.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Entry {
-    patterns: Vec<SevenSegment>,
-    outputs: Vec<SevenSegment>,
-    mappings: Vec<(char, char)>
+pub struct DisplayTable {
+    segments: Vec<char>,
+    // (glyph, mask of lit segments)
+    glyphs: Vec<(char, u32)>,
+    art: Vec<String>,
+    strokes: Vec<char>,
 }
 
-impl Entry {
+impl DisplayTable {
 
-    pub fn parse(line: &str) -> Entry {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"([a-g\s]+)\s\|\s([a-g\s]+)").unwrap();
+    pub fn new(segments: &str, glyphs: &[(char, &str)]) -> DisplayTable {
+        let mut table = DisplayTable { segments: segments.chars().collect(), glyphs: vec![], art: vec![], strokes: vec![] };
+        assert!(table.segments.len() <= 32, "At most 32 segments are supported");
+        table.glyphs = glyphs.iter()
+            .map(|&(d, g)| (d, table.mask(&SevenSegment::new(g)).unwrap()))
+            .collect();
+        for (i, g) in table.glyphs.iter().enumerate() {
+            assert!(table.glyphs[..i].iter().all(|o| o.1 != g.1), "Glyph '{}' duplicates another", g.0);
         }
-        // Parse line (ex: "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf")
-        let caps = RE.captures(line).unwrap();
-        let patterns: Vec<SevenSegment> = caps.get(1).unwrap().as_str().split_whitespace().map(|t| SevenSegment::new(t)).collect();
-        assert_eq!(patterns.len(), 10);
-        let outputs: Vec<SevenSegment> = caps.get(2).unwrap().as_str().split_whitespace().map(|t| SevenSegment::new(t)).collect();
-        assert_eq!(outputs.len(), 4);
-        
-        let mappings = Entry::create_mapping(&patterns);
-        Entry { patterns, outputs, mappings: mappings }
-    }
-
-    pub fn counts(patterns: &Vec<SevenSegment>, c: char) -> usize {
-        patterns.iter().map(|p| p.segments.clone()).flatten().filter(|&s| s==c).count()
-    }
-
-    pub fn create_mapping(patterns: &Vec<SevenSegment>) -> Vec<(char, char)> {
-        let mut mappings = vec![];
-        assert_eq!(patterns.len(), 10, "There must be 10 patterns");
-        // first map the unique segments by their occurrence counts
-        let mut unused = vec![];
-        for c in vec!['a','b','c','d','e','f','g'] {
-            match Entry::counts(&patterns, c) {
-                4 => mappings.push(('e', c)),
-                6 => mappings.push(('b', c)),
-                9 => mappings.push(('f', c)),
-                _ => unused.push(c),
-            }
+        table
+    }
+
+    // strokes holds the character drawn for each segment, in segment order
+    pub fn with_art(mut self, template: &str, strokes: &str) -> DisplayTable {
+        assert_eq!(strokes.chars().count(), self.segments.len(), "Need one stroke per segment");
+        self.art = template.lines().map(String::from).collect();
+        self.strokes = strokes.chars().collect();
+        self
+    }
+
+    pub fn seven_segment() -> DisplayTable {
+        DisplayTable::new("abcdefg", &[
+            ('0', "abcefg"), ('1', "cf"), ('2', "acdeg"), ('3', "acdfg"), ('4', "bcdf"),
+            ('5', "abdfg"), ('6', "abdefg"), ('7', "acf"), ('8', "abcdefg"), ('9', "abcdfg"),
+        ]).with_art(" aaaa \nb    c\nb    c\n dddd \ne    f\ne    f\n gggg ", "-||-||-")
+    }
+
+    pub fn fourteen_segment() -> DisplayTable {
+        DisplayTable::new("abcdefghijklmn", &FOURTEEN_SEGMENT)
+            .with_art(" aaaaa \nfi j kb\nf ijk b\n gg hh \ne lmn c\nel m nc\n ddddd ", "-||-||--\\|//|\\")
+    }
+
+    pub fn sixteen_segment() -> DisplayTable {
+        let glyphs: Vec<(char, String)> = FOURTEEN_SEGMENT.iter()
+            .map(|&(c, g)| match SIXTEEN_OVERRIDES.iter().find(|o| o.0 == c) {
+                Some(o) => (c, o.1.to_string()),
+                None => (c, g.chars()
+                    .flat_map(|s| SIXTEEN_FROM_FOURTEEN.iter().find(|m| m.0 == s).unwrap().1.chars())
+                    .collect()),
+            })
+            .collect();
+        let glyphs: Vec<(char, &str)> = glyphs.iter().map(|(c, g)| (*c, g.as_str())).collect();
+        DisplayTable::new("abcdefghkmnprstu", &glyphs)
+            .with_art(" aa bb \nhk m nc\nh kmn c\n uu pp \ng tsr d\ngt s rd\n ff ee ", "--||--||\\|/-\\|/-")
+    }
+
+    // Display with the given number of segments (ex: 7, 14 or 16)
+    pub fn with_segments(count: usize) -> Option<DisplayTable> {
+        match count {
+            7 => Some(DisplayTable::seven_segment()),
+            14 => Some(DisplayTable::fourteen_segment()),
+            16 => Some(DisplayTable::sixteen_segment()),
+            _ => None,
         }
-        // then segment 'c' is the only unused letter in the pattern where the count is 2
-        let mut unused2 = vec![];
-        let p = patterns.iter().find(|p| p.len()==2).unwrap();
-        for u in &unused {
-            if p.segments.contains(u) {
-                mappings.push(('c', *u));
-            } else {
-                unused2.push(*u);
+    }
+
+    pub fn mask(&self, segment: &SevenSegment) -> Result<u32, MappingError> {
+        segment.segments.iter().try_fold(0, |acc, c| {
+            match self.segments.iter().position(|s| s == c) {
+                Some(i) => Ok(acc | 1 << i),
+                None => Err(MappingError::UnknownSegment(*c)),
             }
+        })
+    }
+
+    pub fn lookup(&self, segment: &SevenSegment) -> Option<char> {
+        let mask = self.mask(segment).ok()?;
+        self.glyphs.iter().find(|&&(_, g)| g == mask).map(|&(d, _)| d)
+    }
+
+    // the segments lit to show a glyph
+    pub fn glyph(&self, glyph: char) -> Option<SevenSegment> {
+        let &(_, mask) = self.glyphs.iter().find(|g| g.0 == glyph)?;
+        Some(SevenSegment {
+            segments: self.segments.iter().enumerate().filter(|&(i, _)| mask & 1 << i != 0).map(|(_, &s)| s).collect(),
+        })
+    }
+
+    // Draw the lit segments of each pattern side by side
+    pub fn render(&self, patterns: &[SevenSegment]) -> String {
+        let masks: Vec<u32> = patterns.iter().map(|p| self.mask(p).unwrap_or(0)).collect();
+        let mut output = vec![];
+        for line in &self.art {
+            let row: Vec<String> = masks.iter().map(|&m| line.chars().map(|c| {
+                match self.segments.iter().position(|&s| s == c) {
+                    Some(i) if m & 1 << i != 0 => self.strokes[i],
+                    Some(_) => ' ',
+                    None => c,
+                }
+            }).collect()).collect();
+            output.push(row.join(" ").trim_end().to_string());
+        }
+        output.join("\n") + "\n"
+    }
+
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MappingError {
+    UnknownSegment(char),
+    // no wiring produces the observed patterns
+    Inconsistent,
+    // more than one wiring fits, with two of them
+    Ambiguous(Vec<Vec<(char, char)>>),
+}
+
+impl fmt::Display for MappingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MappingError::UnknownSegment(c) => write!(f, "Unknown segment '{}'", c),
+            MappingError::Inconsistent => write!(f, "No wiring matches the patterns"),
+            MappingError::Ambiguous(m) => write!(f, "Several wirings match the patterns, ex: {:?}", m),
+        }
+    }
+}
+
+// Search for every wire -> segment permutation which turns each observed
+// pattern into a glyph of the table, stopping once a second one turns up.
+struct WireSolver<'a> {
+    table: &'a DisplayTable,
+    patterns: Vec<u32>,
+    solutions: Vec<Vec<usize>>,
+}
+
+impl WireSolver<'_> {
+
+    // give each pattern a distinct glyph of the same size, narrowing the
+    // segments each wire could drive as we go
+    fn assign(&mut self, i: usize, used: &mut Vec<bool>, candidates: &[u32]) {
+        if self.solutions.len() > 1 {
+            return;
         }
-    
-        // then segment 'a' is the only unused letter in the pattern where the count is 3
-        let mut unused3 = vec![];
-        let p = patterns.iter().find(|p| p.len()==3).unwrap();
-        for u in &unused2 {
-            if p.segments.contains(u) {
-                mappings.push(('a', *u));
-            } else {
-                unused3.push(*u);
+        if i == self.patterns.len() {
+            self.permutations(0, 0, candidates, &mut vec![0; candidates.len()]);
+            return;
+        }
+        let pattern = self.patterns[i];
+        for g in 0..self.table.glyphs.len() {
+            let glyph = self.table.glyphs[g].1;
+            if used[g] || glyph.count_ones() != pattern.count_ones() {
+                continue;
+            }
+            let narrowed: Vec<u32> = candidates.iter().enumerate()
+                .map(|(w, &c)| if pattern & 1 << w != 0 { c & glyph } else { c & !glyph })
+                .collect();
+            if narrowed.contains(&0) {
+                continue;
             }
+            used[g] = true;
+            self.assign(i + 1, used, &narrowed);
+            used[g] = false;
+        }
+    }
+
+    fn permutations(&mut self, wire: usize, taken: u32, candidates: &[u32], perm: &mut Vec<usize>) {
+        if self.solutions.len() > 1 {
+            return;
         }
-            
-        // then segment 'd' is the only unused letter in the pattern where the count is 4
-        let mut unused4 = vec![];
-        let p = patterns.iter().find(|p| p.len()==4).unwrap();
-        for u in &unused3 {
-            if p.segments.contains(u) {
-                mappings.push(('d', *u));
-            } else {
-                unused4.push(*u);
+        if wire == candidates.len() {
+            self.solutions.push(perm.clone());
+            return;
+        }
+        for s in 0..candidates.len() {
+            if candidates[wire] & !taken & 1 << s != 0 {
+                perm[wire] = s;
+                self.permutations(wire + 1, taken | 1 << s, candidates, perm);
             }
         }
-        // that only leaves segment 'g'
-        mappings.push(('g', unused4[0]));
-        return mappings;
+    }
+
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    patterns: Vec<SevenSegment>,
+    outputs: Vec<SevenSegment>,
+    mappings: Vec<(char, char)>,
+    table: DisplayTable,
+}
+
+impl Entry {
+
+    // Parse line (ex: "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf")
+    pub fn parse(line: &str, table: &DisplayTable) -> Result<Entry, MappingError> {
+        let (patterns, outputs) = line.split_once('|').unwrap_or((line, ""));
+        let patterns: Vec<SevenSegment> = patterns.split_whitespace().map(SevenSegment::new).collect();
+        let outputs: Vec<SevenSegment> = outputs.split_whitespace().map(SevenSegment::new).collect();
+
+        // the outputs are observations of the same wiring too
+        let observed: Vec<SevenSegment> = patterns.iter().chain(&outputs).cloned().collect();
+        let mappings = Entry::create_mapping(&observed, table)?;
+        Ok(Entry { patterns, outputs, mappings, table: table.clone() })
+    }
+
+    // Work out the (segment, wire) pairs from any number of observed patterns
+    pub fn create_mapping(patterns: &[SevenSegment], table: &DisplayTable) -> Result<Vec<(char, char)>, MappingError> {
+        let mut masks = patterns.iter().map(|p| table.mask(p)).collect::<Result<Vec<_>, _>>()?;
+        masks.sort_unstable();
+        masks.dedup();
+        // the patterns with the fewest possible glyphs narrow the search quickest
+        masks.sort_by_key(|m| table.glyphs.iter().filter(|g| g.1.count_ones() == m.count_ones()).count());
+
+        let n = table.segments.len();
+        let mut solver = WireSolver { table, patterns: masks, solutions: vec![] };
+        solver.assign(0, &mut vec![false; table.glyphs.len()], &vec![(1u64 << n).wrapping_sub(1) as u32; n]);
+
+        let to_mapping = |perm: &Vec<usize>| -> Vec<(char, char)> {
+            perm.iter().enumerate().map(|(w, &s)| (table.segments[s], table.segments[w])).collect()
+        };
+        match solver.solutions.len() {
+            0 => Err(MappingError::Inconsistent),
+            1 => Ok(to_mapping(&solver.solutions[0])),
+            _ => Err(MappingError::Ambiguous(solver.solutions.iter().map(to_mapping).collect())),
+        }
     }
 
     pub fn unmap(&self, c: char) -> char {
@@ -126,20 +294,30 @@ impl Entry {
         SevenSegment { segments }
     }
 
+    // the glyphs shown on the outputs
+    pub fn decode(&self) -> String {
+        self.outputs.iter().map(|o| self.table.lookup(&self.fix(o)).unwrap()).collect()
+    }
+
     pub fn number(&self) -> usize {
-        concat(&self.outputs.iter().map(|o| SevenSegment::get_number(self.fix(o)).unwrap()).collect())
+        concat(&self.decode().chars().map(|d| d.to_digit(10).unwrap() as usize).collect::<Vec<_>>())
+    }
+
+    // the unscrambled outputs drawn with the display's art
+    pub fn render(&self) -> String {
+        self.table.render(&self.outputs.iter().map(|o| self.fix(o)).collect::<Vec<_>>())
     }
 
 }
 
 // convert vec![1, 2, 3, 4] to 1234
-fn concat(numbers: &Vec<usize>) -> usize {
+fn concat(numbers: &[usize]) -> usize {
     numbers.iter().fold(0, |acc, n| acc * 10 + n)
 }
 
 pub fn day8(args: &[String]) -> i32 {
     println!("Day 8");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -148,13 +326,35 @@ pub fn day8(args: &[String]) -> i32 {
     let contents = fs::read_to_string(filename)
         .expect("Something went wrong reading the file");
 
-    let entries: Vec<_> = contents.lines().map(|l| Entry::parse(l)).collect();
+    let segments = utils::option(args, "--display").map_or(7, |d| d.parse().unwrap());
+    let table = match DisplayTable::with_segments(segments) {
+        Some(t) => t,
+        None => {
+            println!("Unsupported display: {} segments", segments);
+            return -1;
+        }
+    };
+    let mut entries = vec![];
+    for (i, l) in contents.lines().enumerate() {
+        match Entry::parse(l, &table) {
+            Ok(e) => entries.push(e),
+            Err(e) => println!("Line {}: {}", i + 1, e),
+        }
+    }
+
+    if utils::flag(args, "--art") {
+        entries.iter().for_each(|e| println!("{}\n{}", e.decode(), e.render()));
+    }
+    if segments != 7 {
+        entries.iter().for_each(|e| println!("{}", e.decode()));
+        return 0;
+    }
 
     println!("Part 1: {}", entries.iter()
         .map(|e| e.outputs.clone()).flatten().filter(|o| o.is_unique()).count());
 
     println!("Part 2: {}", entries.iter()
         .map(|e| e.number()).sum::<usize>());
-    
+
     0
 }
@@ -1,5 +1,7 @@
 use std::fs;
+use std::collections::HashSet;
 use itertools::iproduct;
+use super::utils;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HeightMap {
@@ -10,6 +12,72 @@ lazy_static! {
     static ref ADJ: Vec<(isize, isize)> = vec![
         (0, 1), (0, -1), (1, 0), (-1, 0)
     ];
+    static ref ADJ8: Vec<(isize, isize)> = vec![
+        (0, 1), (0, -1), (1, 0), (-1, 0),
+        (1, 1), (1, -1), (-1, 1), (-1, -1)
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Basin {
+    pub size: usize,
+    // (y, x) of the lowest cell, the first found on a tie
+    pub low_point: (usize, usize),
+    // (y, x) of the top left and bottom right corners
+    pub bounds: ((usize, usize), (usize, usize)),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Basins {
+    // basin index of each cell, None for walls
+    pub labels: Vec<Vec<Option<usize>>>,
+    pub basins: Vec<Basin>,
+}
+
+// ANSI colour codes and matching RGB values used to tell basins apart
+const PALETTE: [(u8, (u8, u8, u8)); 12] = [
+    (31, (205, 49, 49)), (32, (13, 188, 121)), (33, (229, 229, 16)), (34, (36, 114, 200)),
+    (35, (188, 63, 188)), (36, (17, 168, 205)), (91, (241, 76, 76)), (92, (35, 209, 139)),
+    (93, (245, 245, 67)), (94, (59, 142, 234)), (95, (214, 112, 214)), (96, (41, 184, 219)),
+];
+
+impl Basins {
+
+    // A colour index for each basin. Every basin has its own colour until the
+    // palette runs out, then the least used colours come round again, still
+    // keeping basins separated by a single wall cell apart where possible.
+    pub fn colours(&self) -> Vec<usize> {
+        let (my, mx) = (self.labels.len(), self.labels[0].len());
+        let mut neighbours: Vec<HashSet<usize>> = vec![HashSet::new(); self.basins.len()];
+        for (y, x) in iproduct!(0..my, 0..mx) {
+            if self.labels[y][x].is_some() {
+                continue;
+            }
+            let around: HashSet<usize> = ADJ8.iter()
+                .map(|(dy, dx)| (y as isize + dy, x as isize + dx))
+                .filter(|&(y, x)| y >= 0 && x >= 0 && y < my as isize && x < mx as isize)
+                .filter_map(|(y, x)| self.labels[y as usize][x as usize])
+                .collect();
+            for &a in &around {
+                neighbours[a].extend(around.iter().filter(|&&b| b != a));
+            }
+        }
+        let mut colours: Vec<usize> = vec![];
+        let mut uses = [0; PALETTE.len()];
+        for n in &neighbours {
+            let used: HashSet<usize> = n.iter().filter(|&&b| b < colours.len()).map(|&b| colours[b]).collect();
+            let colour = (0..PALETTE.len()).filter(|c| !used.contains(c)).min_by_key(|&c| uses[c])
+                .unwrap_or_else(|| (0..PALETTE.len()).min_by_key(|&c| uses[c]).unwrap());
+            uses[colour] += 1;
+            colours.push(colour);
+        }
+        colours
+    }
+
+    fn is_low_point(&self, y: usize, x: usize) -> bool {
+        matches!(self.labels[y][x], Some(l) if self.basins[l].low_point == (y, x))
+    }
+
 }
 
 impl HeightMap {
@@ -52,36 +120,108 @@ impl HeightMap {
         l
     }
 
-    pub fn basin_size(&self, used: &mut Vec<Vec<bool>>, y: isize, x: isize) -> usize {
-        let (uy, ux) = (y as usize, x as usize);
-        if self.map[uy][ux] == 9 || used[uy][ux] {
-            return 0;
+    // Label the connected areas lower than `wall`, joining diagonal
+    // neighbours as well when `diagonal` is set
+    pub fn basins(&self, wall: u32, diagonal: bool) -> Basins {
+        let (my, mx) = self.size();
+        let adj: &Vec<(isize, isize)> = if diagonal { &ADJ8 } else { &ADJ };
+        let mut labels = vec![vec![None; mx]; my];
+        let mut basins = vec![];
+        for (y, x) in iproduct!(0..my, 0..mx) {
+            if self.map[y][x] >= wall || labels[y][x].is_some() {
+                continue;
+            }
+            let label = basins.len();
+            let mut basin = Basin { size: 0, low_point: (y, x), bounds: ((y, x), (y, x)) };
+            labels[y][x] = Some(label);
+            let mut stack = vec![(y, x)];
+            while let Some((cy, cx)) = stack.pop() {
+                basin.size += 1;
+                let (ly, lx) = basin.low_point;
+                if self.map[cy][cx] < self.map[ly][lx] || (self.map[cy][cx] == self.map[ly][lx] && (cy, cx) < (ly, lx)) {
+                    basin.low_point = (cy, cx);
+                }
+                let ((y0, x0), (y1, x1)) = basin.bounds;
+                basin.bounds = ((y0.min(cy), x0.min(cx)), (y1.max(cy), x1.max(cx)));
+                for (dy, dx) in adj.iter() {
+                    let (ny, nx) = (cy as isize + dy, cx as isize + dx);
+                    match self.value(ny, nx) {
+                        Some(v) if v < wall && labels[ny as usize][nx as usize].is_none() => {
+                            labels[ny as usize][nx as usize] = Some(label);
+                            stack.push((ny as usize, nx as usize));
+                        },
+                        _ => (),
+                    }
+                }
+            }
+            basins.push(basin);
         }
-        used[uy][ux] = true;
-        ADJ.iter().map(|(dy, dx)| (y + dy, x + dx))
-            .filter(|&(y, x)| self.value(y, x).is_some())
-            .map(|(y, x)| self.basin_size(used, y, x)).sum::<usize>() + 1   
+        Basins { labels, basins }
     }
 
 }
 
 
+impl HeightMap {
+
+    // Draw each basin's heights in its own colour with the low points as
+    // '*', or without colour each basin as a letter, upper case at the low
+    // point, and the walls as '#'
+    pub fn render(&self, basins: &Basins, colour: bool) -> String {
+        let colours = basins.colours();
+        let mut output = vec![];
+        for (y, row) in self.map.iter().enumerate() {
+            let mut line = String::new();
+            for (x, &h) in row.iter().enumerate() {
+                let low = basins.is_low_point(y, x);
+                line.push_str(&match (basins.labels[y][x], colour) {
+                    (None, true) => format!("\x1b[2m{}\x1b[0m", h),
+                    (None, false) => String::from("#"),
+                    (Some(l), true) => match low {
+                        true => format!("\x1b[1;{}m*\x1b[0m", PALETTE[colours[l]].0),
+                        false => format!("\x1b[{}m{}\x1b[0m", PALETTE[colours[l]].0, h),
+                    },
+                    (Some(l), false) => {
+                        let c = (b'a' + colours[l] as u8) as char;
+                        if low { c.to_ascii_uppercase() } else { c }.to_string()
+                    },
+                });
+            }
+            output.push(line);
+        }
+        output.join("\n") + "\n"
+    }
+
+    // Plain PPM image of the basins, with black walls and white low points
+    pub fn to_ppm(&self, basins: &Basins) -> String {
+        let colours = basins.colours();
+        let (my, mx) = self.size();
+        let header = format!("P3\n{} {}\n255\n", mx, my);
+        utils::netpbm(header, iproduct!(0..my, 0..mx)
+            .map(|(y, x)| match basins.labels[y][x] {
+                None => (0, 0, 0),
+                Some(_) if basins.is_low_point(y, x) => (255, 255, 255),
+                Some(l) => PALETTE[colours[l]].1,
+            })
+            .flat_map(|(r, g, b)| [r, g, b])
+            .map(|v| v.to_string()))
+    }
+
+}
+
 pub fn part1(hm: &HeightMap) -> u32 {
     hm.low_points().iter().map(|&(y, x)| hm.value(y, x).unwrap() + 1).sum()
 }
 
 pub fn part2(hm: &HeightMap) -> usize {
-    let (y, x) = hm.size();
-    let mut used = vec![vec![false; x]; y];
-    let mut sizes: Vec<usize> = hm.low_points().iter()
-        .map(|&(y, x)| hm.basin_size(&mut used, y, x)).collect();
+    let mut sizes: Vec<usize> = hm.basins(9, false).basins.iter().map(|b| b.size).collect();
     sizes.sort();
     sizes.iter().rev().take(3).product()
 }
 
 pub fn day9(args: &[String]) -> i32 {
     println!("Day 9");
-    if args.len() != 1 {
+    if args.is_empty() {
         println!("Missing input file");
         return -1;
     }
@@ -94,6 +234,14 @@ pub fn day9(args: &[String]) -> i32 {
 
     println!("Part 1: {}", part1(&hm));
     println!("Part 2: {}", part2(&hm));
+
+    if utils::flag(args, "--render") {
+        print!("{}", hm.render(&hm.basins(9, false), !utils::flag(args, "--no-colour")));
+    }
+    if let Some(filename) = utils::option(args, "--ppm") {
+        fs::write(filename, hm.to_ppm(&hm.basins(9, false))).expect("Unable to write image");
+        println!("Wrote {}", filename);
+    }
     
     0
 }
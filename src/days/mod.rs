@@ -1,4 +1,4 @@
-// pub mod utils;
+pub mod utils;
 pub mod day1;
 pub mod day2;
 pub mod day3;
@@ -0,0 +1,99 @@
+// Helpers shared between the days
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+// optional arguments following the input file,
+// ex: "day4 data/day4.txt --rules rows,diagonals --replay"
+pub fn flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == name)
+}
+
+pub fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|v| v.as_str())
+}
+
+// Plain netpbm (PGM/PPM) image text: the values follow the header, keeping
+// lines within the 70 characters the format allows
+pub fn netpbm(header: String, values: impl Iterator<Item = String>) -> String {
+    let mut output = header;
+    let mut line_len = 0;
+    for v in values {
+        if line_len > 0 && line_len + v.len() + 1 > 70 {
+            output.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            output.push(' ');
+            line_len += 1;
+        }
+        line_len += v.len();
+        output.push_str(&v);
+    }
+    output.push('\n');
+    output
+}
+
+// Playback of a grid simulation, one frame per step: redrawn in place on the
+// terminal with `delay` between frames, and/or saved as numbered text files.
+// ex: "day11 data/day11.txt --animate 50 --frames /tmp/octopus"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+    delay: Option<Duration>,
+    frames: Option<PathBuf>,
+    count: usize,
+}
+
+impl Animation {
+
+    pub fn new(delay: Option<Duration>, frames: Option<PathBuf>) -> Animation {
+        if let Some(dir) = &frames {
+            fs::create_dir_all(dir).expect("Unable to create the frames directory");
+        }
+        Animation { delay, frames, count: 0 }
+    }
+
+    // --animate [delay in ms, 100 by default] and --frames dir, or None when
+    // neither is given
+    pub fn from_args(args: &[String]) -> Option<Animation> {
+        let delay = match flag(args, "--animate") {
+            true => Some(Duration::from_millis(option(args, "--animate").and_then(|d| d.parse().ok()).unwrap_or(100))),
+            false => None,
+        };
+        let frames = option(args, "--frames").map(PathBuf::from);
+        match (delay, frames) {
+            (None, None) => None,
+            (delay, frames) => Some(Animation::new(delay, frames)),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn show(&mut self, title: &str, frame: &str) {
+        let text = format!("{}\n{}", title, frame);
+        if let Some(delay) = self.delay {
+            let mut stdout = std::io::stdout().lock();
+            // clear the screen and start from the top left corner, as moving
+            // up over the last frame stops at the top of the screen when the
+            // frame is taller than the terminal
+            write!(stdout, "\x1b[H\x1b[2J{}", text).unwrap();
+            if !text.ends_with('\n') {
+                writeln!(stdout).unwrap();
+            }
+            stdout.flush().unwrap();
+            thread::sleep(delay);
+        }
+        if let Some(dir) = &self.frames {
+            fs::write(dir.join(format!("frame{:04}.txt", self.count)), text).expect("Unable to write frame");
+        }
+        self.count += 1;
+    }
+
+}
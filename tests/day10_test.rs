@@ -0,0 +1,85 @@
+use std::fs;
+use aoc21::days::day10::{self, CheckError, CheckResult, Edit, Language, Repair};
+
+#[test]
+fn day10_example() {
+    let language = Language::standard();
+    let results: Vec<CheckResult> = fs::read_to_string("data/day10example.txt").unwrap().lines()
+        .map(|l| language.check(l).unwrap())
+        .collect();
+    assert_eq!(results.iter().filter_map(|r| language.corrupted_score(r)).sum::<u64>(), 26397);
+    let mut completions: Vec<u64> = results.iter().filter_map(|r| language.completion_score(r)).collect();
+    completions.sort();
+    assert_eq!(completions, vec![294, 5566, 288957, 995444, 1480781]);
+}
+
+#[test]
+fn day10_results() {
+    let language = Language::standard();
+    assert_eq!(language.check("{([(<{}[<>[]}>{[]{[(<()>"),
+        Ok(CheckResult::Corrupted { column: 12, expected: Some(']'), found: '}' }));
+    assert_eq!(language.check("()>"), Ok(CheckResult::Corrupted { column: 2, expected: None, found: '>' }));
+    assert_eq!(language.check("[({(<(())[]>[[{[]{<()<>>"),
+        Ok(CheckResult::Incomplete { completion: String::from("}}]])})]") }));
+    assert_eq!(language.check("<([]){()}[{}]>"), Ok(CheckResult::Valid));
+    assert_eq!(language.check("(a)"), Err(CheckError::UnknownChar { column: 1, found: 'a' }));
+    assert_eq!(day10::part1("{([(<{}[<>[]}>{[]{[(<()>"), Ok(Some(1197)));
+    assert_eq!(day10::part2("<{([{{}}[<[[[<>{}]]]>[]]"), Ok(Some(294)));
+    assert_eq!(day10::part1("(a)"), Err(CheckError::UnknownChar { column: 1, found: 'a' }));
+    assert_eq!(day10::part2("(a"), Err(CheckError::UnknownChar { column: 1, found: 'a' }));
+}
+
+#[test]
+fn day10_custom_language() {
+    let language = Language::new("/\\()").with_scores(&[7, 11], &[2, 3]);
+    assert_eq!(language.check("/(\\"), Ok(CheckResult::Corrupted { column: 2, expected: Some(')'), found: '\\' }));
+    assert_eq!(language.corrupted_score(&language.check("/(\\").unwrap()), Some(7));
+    assert_eq!(language.completion_score(&language.check("(/").unwrap()), Some(2 * 5 + 3));
+    assert!(language.check("<>").is_err());
+}
+
+#[test]
+fn day10_repair() {
+    let language = Language::standard();
+    assert_eq!(language.repair("<[]>"), Ok(Repair::Valid));
+    assert_eq!(language.repair("[<>({}){}[([])<>]]((<").unwrap(), Repair::Completed(String::from("[<>({}){}[([])<>]]((<>))")));
+    let fixes = match language.repair("[[<[([]))<([[{}[[()]]]").unwrap() {
+        Repair::Fixes(f) => f,
+        r => panic!("Unexpected repair {:?}", r),
+    };
+    let edits: Vec<Edit> = fixes.iter().map(|(e, _)| *e).collect();
+    assert_eq!(edits, vec![
+        Edit::Replace { column: 8, with: ']' },
+        Edit::Replace { column: 8, with: '(' },
+        Edit::Replace { column: 8, with: '[' },
+        Edit::Replace { column: 8, with: '{' },
+        Edit::Replace { column: 8, with: '<' },
+        Edit::Delete { column: 8 },
+        Edit::Insert { column: 8, with: '(' },
+        Edit::Replace { column: 7, with: '(' },
+        Edit::Insert { column: 7, with: '(' },
+        Edit::Insert { column: 5, with: '(' },
+        Edit::Replace { column: 3, with: '(' },
+    ]);
+    assert_eq!(fixes[0].1, "[[<[([])]<([[{}[[()]]]])>>]]");
+    for (_, line) in &fixes {
+        assert_eq!(language.check(line), Ok(CheckResult::Valid));
+    }
+    // the bracket to change can come before the corruption
+    assert_eq!(language.repair("((([[)))"), Ok(Repair::Fixes(vec![
+        (Edit::Replace { column: 4, with: ']' }, String::from("((([])))")),
+    ])));
+    assert_eq!(language.repair("(]]"), Ok(Repair::Fixes(vec![
+        (Edit::Replace { column: 1, with: '[' }, String::from("([])")),
+    ])));
+    // too many stray closers for one change to fix
+    assert_eq!(language.repair("(]]]"), Ok(Repair::Fixes(vec![])));
+}
+
+#[test]
+fn day10_format() {
+    let language = Language::standard();
+    assert_eq!(language.format("[(<>{})]", 2).unwrap(), "[\n  (\n    <>\n    {}\n  )\n]\n");
+    assert_eq!(language.format("{(", 1).unwrap(), "{\n (\n");
+    assert!(language.format("(x)", 2).is_err());
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use aoc21::days::day11::{EnergyMap, FlashEvent, FlashRules, Neighbourhood};
+use aoc21::days::utils::Animation;
+
+#[test]
+fn day11_example() {
+    let mut em = EnergyMap::parse(&fs::read_to_string("data/day11example.txt").unwrap());
+    (0..10).for_each(|_| { em.next(); });
+    assert_eq!(em.flashes(), 204);
+    (10..100).for_each(|_| { em.next(); });
+    assert_eq!(em.flashes(), 1656);
+}
+
+#[test]
+fn day11_waves() {
+    let mut em = EnergyMap::parse(&fs::read_to_string("data/day11example0.txt").unwrap()).with_log();
+    let waves = em.step();
+    assert_eq!(waves.len(), 2);
+    assert_eq!(waves[1], vec![(2, 2)]);
+    assert_eq!(em.events().len(), 9);
+    assert_eq!(em.events()[8], FlashEvent { step: 1, wave: 1, cell: (2, 2) });
+    em.step();
+    assert!(em.events().iter().all(|e| e.step == 1));
+}
+
+#[test]
+fn day11_rules() {
+    // a lone 9 flashes on its own with the orthogonal neighbourhood, but
+    // wrapping round it also sets off the 8 at the other end of the row
+    let rules = FlashRules::new().with_neighbourhood(Neighbourhood::Orthogonal);
+    let mut em = EnergyMap::parse("9008\n0000").with_rules(rules.clone());
+    assert_eq!(em.step(), vec![vec![(0, 0)]]);
+    let mut em = EnergyMap::parse("9008\n0000").with_rules(rules.clone().with_wrap(true));
+    assert_eq!(em.step(), vec![vec![(0, 0)], vec![(0, 3)]]);
+    assert_eq!(em.adjacent(0, 0), vec![(0, 1), (0, 3), (1, 0)]);
+    assert_eq!(Neighbourhood::parse("4"), Some(Neighbourhood::Orthogonal));
+    assert_eq!(Neighbourhood::parse("hex"), None);
+
+    let mut em = EnergyMap::parse("11\n34").with_rules(rules.with_threshold(4).with_reset(1));
+    assert!(!em.next());
+    assert_eq!(em.to_string(), "33\n11\n");
+}
+
+#[test]
+fn day11_cycle() {
+    let start = EnergyMap::parse(&fs::read_to_string("data/day11example.txt").unwrap());
+    let cycle = start.find_cycle(1000).unwrap();
+    assert_eq!(cycle.first_sync(), Some(195));
+    assert_eq!((cycle.start, cycle.length), (195, 10));
+    assert_eq!(cycle.flashes_after(0), 0);
+    let mut em = start.clone();
+    for steps in 1..=400 {
+        em.next();
+        assert_eq!(cycle.flashes_after(steps), em.flashes() as u128);
+    }
+    assert_eq!(cycle.flashes_after(1_000_000_000_195), cycle.flashes_after(195) + 100 * 100_000_000_000);
+    assert_eq!(start.find_cycle(100), None);
+
+    // two octopuses which never flash in the same step
+    let cycle = EnergyMap::parse("90").with_rules(FlashRules::new().with_threshold(11)).find_cycle(100).unwrap();
+    assert_eq!(cycle.first_sync(), None);
+}
+
+#[test]
+fn day11_frames() {
+    let dir = std::env::temp_dir().join(format!("day11_frames_{}", std::process::id()));
+    let mut animation = Animation::new(None, Some(dir.clone()));
+    let mut em = EnergyMap::parse(&fs::read_to_string("data/day11example0.txt").unwrap());
+    for step in 0..3 {
+        animation.show(&format!("Step {}", step), &em.to_string());
+        em.next();
+    }
+    assert_eq!(animation.count(), 3);
+    assert_eq!(fs::read_to_string(dir.join("frame0001.txt")).unwrap(), "Step 1\n34543\n40004\n50005\n40004\n34543\n");
+    assert!(!dir.join("frame0003.txt").exists());
+    fs::remove_dir_all(dir).unwrap();
+
+    let args: Vec<String> = ["input.txt", "--animate", "--wrap"].iter().map(|a| a.to_string()).collect();
+    assert_eq!(Animation::from_args(&args), Some(Animation::new(Some(std::time::Duration::from_millis(100)), None)));
+    assert_eq!(Animation::from_args(&args[..1]), None);
+}
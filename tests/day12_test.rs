@@ -0,0 +1,131 @@
+use std::fs;
+use std::collections::HashMap;
+use aoc21::days::day12::{self, Cave, CaveGraph, PathError, VisitPolicy};
+
+fn graph(name: &str) -> CaveGraph {
+    CaveGraph::parse(&fs::read_to_string(format!("data/{}.txt", name)).unwrap())
+}
+
+#[test]
+fn day12_examples() {
+    for (name, once, twice) in [("day12example0", 10, 36), ("day12example1", 19, 103), ("day12example", 226, 3509)] {
+        let graph = graph(name);
+        assert_eq!(graph.count_paths(&VisitPolicy::new()).unwrap(), once);
+        assert_eq!(graph.count_paths(&VisitPolicy::part2()).unwrap(), twice);
+        assert_eq!(graph.paths(&VisitPolicy::part2()).unwrap().len() as u64, twice);
+        let connections = graph.connections();
+        assert_eq!(day12::find_paths_part1(&connections, vec![Cave::start()]).len() as u64, once);
+        assert_eq!(day12::find_paths_part2(&connections, HashMap::new(), vec![Cave::start()]).len() as u64, twice);
+    }
+}
+
+#[test]
+fn day12_diamonds() {
+    // a chain of 12 diamonds, each offering two ways round
+    let mut input = String::from("start-p0\np12-end\n");
+    for i in 0..12 {
+        input += &format!("p{0}-q{0}\np{0}-r{0}\nq{0}-p{1}\nr{0}-p{1}\n", i, i + 1);
+    }
+    let graph = CaveGraph::parse(&input);
+    assert_eq!(graph.len(), 39);
+    assert_eq!(graph.count_paths(&VisitPolicy::new()).unwrap(), 1 << 12);
+    assert_eq!(graph.paths(&VisitPolicy::new()).unwrap().len(), 1 << 12);
+    assert!(graph.count_paths(&VisitPolicy::part2()).unwrap() > 1 << 12);
+}
+
+#[test]
+fn day12_many_caves() {
+    // more caves than fit in a u64 bit set, the small ones between big ones
+    let big = |i: usize| format!("{}{}", (b'A' + (i / 26) as u8) as char, (b'A' + (i % 26) as u8) as char);
+    let mut input = format!("start-{}\nb39-end\n", big(0));
+    for i in 0..40 {
+        input += &format!("{}-b{}\nb{}-{}\n", big(i), i, i, big(i + 1));
+    }
+    let graph = CaveGraph::parse(&input);
+    assert_eq!(graph.len(), 83);
+    assert_eq!(graph.count_paths(&VisitPolicy::new()), Ok(1));
+    let twice = day12::find_paths_part2(&graph.connections(), HashMap::new(), vec![Cave::start()]).len() as u64;
+    assert_eq!(graph.count_paths(&VisitPolicy::part2()), Ok(twice));
+}
+
+#[test]
+fn day12_many_small_caves() {
+    // more small cave counts than fit in a single word
+    let mut input = String::from("start-s0\ns69-end\n");
+    for i in 0..69 {
+        input += &format!("s{}-s{}\n", i, i + 1);
+    }
+    let graph = CaveGraph::parse(&input);
+    assert_eq!(graph.count_paths(&VisitPolicy::new()), Ok(1));
+    assert_eq!(graph.paths(&VisitPolicy::part2()).unwrap().len(), 1);
+
+    // excursions from A into dead ends: with room for two of them there are
+    // 1 + n + n * n paths, less the n repeats when each cave is visited once
+    let mut input = String::from("start-A\nA-end\n");
+    for i in 0..40 {
+        input += &format!("A-b{}\n", i);
+    }
+    let graph = CaveGraph::parse(&input);
+    assert_eq!(graph.count_paths(&VisitPolicy::new().with_max_length(6)), Ok(1 + 40 + 40 * 39));
+    assert_eq!(graph.count_paths(&VisitPolicy::new().with_small_limit(3).with_max_length(6)), Ok(1 + 40 + 40 * 40));
+    assert_eq!(graph.paths(&VisitPolicy::new().with_small_limit(3).with_max_length(6)).unwrap().len(), 1 + 40 + 40 * 40);
+}
+
+#[test]
+fn day12_policies() {
+    let graph = graph("day12example0");
+    let forbidden = VisitPolicy::new().with_forbidden("c", "A");
+    assert_eq!(graph.count_paths(&forbidden).unwrap(), 5);
+    assert!(graph.paths(&forbidden).unwrap().iter().all(|p| !p.contains(&&Cave::new("c"))));
+    assert_eq!(graph.count_paths(&VisitPolicy::new().with_max_length(2)).unwrap(), 2);
+    // every small cave twice allows more than a single one twice
+    let twice = VisitPolicy::new().with_small_limit(2);
+    assert!(graph.count_paths(&twice).unwrap() > graph.count_paths(&VisitPolicy::part2()).unwrap());
+
+    // c bounces back and forth with A, so the length has to be capped
+    let revisit = VisitPolicy::new().with_revisitable("c").with_max_length(6);
+    let paths = graph.paths(&revisit).unwrap();
+    assert_eq!(graph.count_paths(&revisit).unwrap(), paths.len() as u64);
+    assert!(paths.iter().any(|p| p.iter().filter(|c| c.name == "c").count() == 2));
+    assert!(paths.iter().all(|p| p.len() <= 7));
+}
+
+#[test]
+fn day12_analysis() {
+    let graph = CaveGraph::parse("start-A\nA-B\nB-end\nA-c\nx-y\n");
+    assert_eq!(graph.degree(graph.id("A").unwrap()), 3);
+    assert_eq!(graph.big_adjacent(), vec![(&Cave::new("A"), &Cave::new("B"))]);
+    assert_eq!(graph.unreachable(), vec![&Cave::new("x"), &Cave::new("y")]);
+    assert_eq!(graph.count_paths(&VisitPolicy::new()), Err(PathError::Infinite(Cave::new("A"), Cave::new("B"))));
+    assert!(graph.paths(&VisitPolicy::part2()).is_err());
+    assert_eq!(graph.count_paths(&VisitPolicy::new().with_forbidden("B", "A")), Ok(0));
+    assert_eq!(graph.count_paths(&VisitPolicy::new().with_max_length(3)), Ok(1));
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("graph caves {\n"));
+    assert!(dot.contains("\"B\" [shape=box, style=bold];"));
+    assert!(dot.contains("\"start\" [shape=doublecircle, style=filled, fillcolor=palegreen];"));
+    assert_eq!(dot.matches(" -- ").count(), 5);
+
+    // A and B can bounce forever but no allowed path gets to them
+    let graph = CaveGraph::parse("start-A\nA-B\nstart-c\nc-end\n");
+    assert!(graph.count_paths(&VisitPolicy::new()).is_err());
+    assert_eq!(graph.count_paths(&VisitPolicy::new().with_forbidden("A", "start")), Ok(1));
+    let graph = CaveGraph::parse("start-c\nc-end\nend-A\nA-B\n");
+    assert_eq!(graph.count_paths(&VisitPolicy::new()), Ok(1));
+    let graph = CaveGraph::parse("start-c\nc-end\nc-A\nA-B\n");
+    assert!(graph.count_paths(&VisitPolicy::new()).is_err());
+    assert_eq!(graph.count_paths(&VisitPolicy::new().with_small_limit(0)), Ok(0));
+}
+
+#[test]
+fn day12_self_loop() {
+    let graph = CaveGraph::parse("start-A\nA-A\nA-end\n");
+    assert_eq!(graph.degree(graph.id("A").unwrap()), 3);
+    assert_eq!(graph.count_paths(&VisitPolicy::new()), Err(PathError::Infinite(Cave::new("A"), Cave::new("A"))));
+    assert_eq!(graph.count_paths(&VisitPolicy::new().with_max_length(3)), Ok(2));
+    // a small cave looping on itself is limited like any other visit
+    let graph = CaveGraph::parse("start-b\nb-b\nb-end\n");
+    assert_eq!(graph.count_paths(&VisitPolicy::new()), Ok(1));
+    assert_eq!(graph.count_paths(&VisitPolicy::part2()), Ok(2));
+}
@@ -0,0 +1,38 @@
+use std::fs;
+use aoc21::days::day3::{self, Diagnostic, Rating};
+
+#[test]
+fn day3_example() {
+    let d = Diagnostic::parse(&fs::read_to_string("data/day3example.txt").unwrap());
+    assert_eq!(d.gamma().value(d.width()), Some(22));
+    assert_eq!(d.epsilon().value(d.width()), Some(9));
+    assert_eq!(d.rating(Rating::OxygenGenerator).unwrap().value(5), Some(23));
+    assert_eq!(d.rating(Rating::CO2Scrubber).unwrap().value(5), Some(10));
+    assert_eq!(d.life_support(), Some(230));
+}
+
+#[test]
+fn day3_wide_rows() {
+    let one = format!("1{}", "0".repeat(99));
+    let zero = "0".repeat(100);
+    let d = Diagnostic::parse(&format!("{}\n{}\n{}\n", one, zero, one));
+    assert_eq!(d.width(), 100);
+    assert_eq!(d.gamma(), day3::BitRow::parse(&one));
+    assert_eq!(d.epsilon().value(d.width()), Some((1 << 99) - 1));
+    assert_eq!(d.rating(Rating::CO2Scrubber).unwrap().to_string(100), zero);
+}
+
+#[test]
+fn day3_custom_criteria() {
+    let d = Diagnostic::parse(&fs::read_to_string("data/day3example.txt").unwrap());
+    let oxygen = d.select(&Rating::OxygenGenerator.criteria()).unwrap();
+    assert_eq!(oxygen.row.value(5), Some(23));
+    let remaining: Vec<_> = oxygen.trace.iter().map(|s| s.remaining).collect();
+    assert_eq!(remaining, vec![7, 4, 3, 2, 1]);
+
+    let criteria = day3::RatingCriteria::new(day3::BitCriteria::MostCommon, true)
+        .with_order(day3::ScanOrder::RightToLeft);
+    let s = d.select(&criteria).unwrap();
+    assert_eq!(s.trace.first().unwrap().column, 4);
+    assert_eq!(s.row.to_string(5), "11110");
+}
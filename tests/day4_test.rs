@@ -0,0 +1,59 @@
+use std::fs;
+use aoc21::days::day4::{BingoBoard, BingoGame, WinRule, Win};
+
+#[test]
+fn day4_rectangular_board() {
+    let mut b = BingoBoard::parse("1 2 3\n4 5 6");
+    assert_eq!(b.size(), (2, 3));
+    assert!(!b.mark(2));
+    assert!(b.mark(5));
+    assert_eq!(b.score(5), Some(14 * 5));
+}
+
+#[test]
+fn day4_win_rules() {
+    let board = BingoBoard::parse("1 2 3\n4 5 6\n7 8 9");
+    let mut b = board.clone().with_rules(&[WinRule::Diagonals]);
+    assert!(!b.mark(1) && !b.mark(5));
+    assert!(b.mark(9));
+
+    let mut b = board.clone().with_rules(&WinRule::parse_list("corners").unwrap());
+    assert!(!b.mark(1) && !b.mark(3) && !b.mark(7));
+    assert!(b.mark(9));
+
+    let mut b = board.with_rules(&WinRule::parse_list("mask=0:1/2:1").unwrap());
+    assert!(!b.mark(2));
+    assert!(b.mark(8));
+    assert_eq!(WinRule::parse("mask=0:x"), None);
+}
+
+#[test]
+fn day4_game() {
+    let game = BingoGame::parse(&fs::read_to_string("data/day4example.txt").unwrap());
+    let result = game.play(false);
+    assert_eq!(result.replay, None);
+    assert_eq!(result.first().unwrap(), (2, Win { draw: 11, number: 24, score: 4512 }));
+    assert_eq!(result.last().unwrap().1.score, 1924);
+    assert_eq!(result.ranking().iter().map(|r| r.0).collect::<Vec<_>>(), vec![2, 0, 1]);
+    assert_eq!(result.ranking_report(), "1. Board 2: won on draw 11 (24), score 4512\n\
+        2. Board 0: won on draw 13 (16), score 2192\n3. Board 1: won on draw 14 (13), score 1924\n");
+
+    let unwinnable = BingoGame::new(vec![1, 2], game.boards().to_vec());
+    let result = unwinnable.play(true);
+    assert!(result.wins.iter().all(|w| w.is_none()));
+    assert!(result.ranking_report().starts_with("-. Board 0: never won\n"));
+    assert_eq!(result.replay.unwrap().len(), 2);
+}
+
+#[test]
+fn day4_many_boards() {
+    // board b holds 25b..25b+25, so it completes its first row on draw 25b+4
+    let boards: Vec<_> = (0..20_000u32)
+        .map(|b| BingoBoard::new((0..5).map(|r| (0..5).map(|c| b * 25 + r * 5 + c).collect()).collect()))
+        .collect();
+    let game = BingoGame::new((0..500_000).collect(), boards);
+    let result = game.play(false);
+    for (b, w) in result.wins.iter().enumerate() {
+        assert_eq!(w.unwrap().draw, b * 25 + 4);
+    }
+}
@@ -7,3 +7,144 @@ fn day5_parse_line() {
     assert_eq!(l.end, (242, 18));
 }
 
+
+fn random_lines(count: usize, size: usize, any_slope: bool) -> Vec<day5::VentLine> {
+    // small LCG so the test needs no extra crates
+    let mut seed: u64 = 12345;
+    let mut next = move |m: usize| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % m
+    };
+    (0..count).map(|_| {
+        let (x, y, len) = (next(size), next(size), next(size) + 1);
+        if any_slope {
+            return day5::VentLine::new((x, y), (next(size), next(size)));
+        }
+        match next(4) {
+            0 => day5::VentLine::new((x, y), (x, y + len)),
+            1 => day5::VentLine::new((x + len, y), (x, y)),
+            2 => day5::VentLine::new((x, y), (x + len, y + len)),
+            _ => day5::VentLine::new((x, y + len), (x + len, y)),
+        }
+    }).collect()
+}
+
+#[test]
+fn day5_sparse_matches_dense() {
+    let lines = random_lines(200, 60, false);
+    for diagonal in [false, true] {
+        let dense = day5::VentMap::new(&lines, diagonal);
+        let sparse = day5::SparseVentMap::new(&lines, diagonal);
+        for min_count in 1..5 {
+            assert_eq!(sparse.overlapping_count(min_count), dense.overlapping_count(min_count));
+        }
+    }
+}
+
+#[test]
+fn day5_sparse_huge_coordinates() {
+    let lines = vec![
+        day5::VentLine::new((0, 1_000_000_000), (2_000_000_000, 1_000_000_000)),
+        day5::VentLine::new((1_000_000_000, 0), (1_000_000_000, 2_000_000_000)),
+        day5::VentLine::new((0, 0), (2_000_000_000, 2_000_000_000)),
+        day5::VentLine::new((1_500_000_000, 1_000_000_000), (1_600_000_000, 1_000_000_000)),
+    ];
+    let sparse = day5::SparseVentMap::new(&lines, true);
+    assert_eq!(sparse.overlapping_count(3), 1);
+    assert_eq!(sparse.overlapping_count(2), 100_000_002);
+}
+
+#[test]
+fn day5_sparse_steep_slopes() {
+    // the only lattice points are the ends, a whole position step apart
+    let line = day5::VentLine::new((0, 0), (2_000_000_000, 1_999_999_999));
+    let side = day5::VentLine::new((2_000_000_000, 0), (2_000_000_000, 2_000_000_000));
+    assert_eq!(line.get_points(true), vec![(0, 0), (2_000_000_000, 1_999_999_999)]);
+    assert_eq!(line.intersections(&line), vec![(0, 0), (2_000_000_000, 1_999_999_999)]);
+    assert_eq!(line.intersections(&side), vec![(2_000_000_000, 1_999_999_999)]);
+    let sparse = day5::SparseVentMap::new(&[line.clone(), line.clone(), side], true);
+    assert_eq!(sparse.overlapping_count(2), 2);
+    assert_eq!(sparse.overlapping_count(3), 1);
+    assert!(!day5::VentLine::new((0, 0), (day5::MAX_COORDINATE + 1, 0)).in_range());
+}
+
+#[test]
+fn day5_sparse_scattered_crosses() {
+    // small crosses spread over a wide area, each crossing once
+    let mut lines = vec![];
+    for i in 0..3000usize {
+        let (x, y) = ((i * 7919) % 1_000_003 * 1000 + 10, (i * 104_729) % 999_983 * 1000 + 10);
+        lines.push(day5::VentLine::new((x - 5, y), (x + 5, y)));
+        lines.push(day5::VentLine::new((x, y - 5), (x, y + 5)));
+    }
+    let sparse = day5::SparseVentMap::new(&lines, false);
+    assert_eq!(sparse.overlapping_count(2), 3000);
+    assert_eq!(sparse.overlapping_count(1), 3000 * 21);
+}
+
+#[test]
+fn day5_sparse_long_lines_apart() {
+    // long diagonals side by side and long horizontals above them, none crossing
+    let mut lines = vec![];
+    for i in 0..2000usize {
+        lines.push(day5::VentLine::new((2 * i, 0), (2 * i + 1_000_000, 1_000_000)));
+        lines.push(day5::VentLine::new((0, 2_000_000 + i), (2_000_000, 2_000_000 + i)));
+    }
+    let sparse = day5::SparseVentMap::new(&lines, true);
+    assert_eq!(sparse.overlapping_count(2), 0);
+    assert_eq!(sparse.overlapping_count(1), 2000 * 1_000_001 + 2000 * 2_000_001);
+}
+
+#[test]
+fn day5_any_slope() {
+    let point = day5::VentLine::new((3, 4), (3, 4));
+    assert_eq!(point.get_points(false), vec![(3, 4)]);
+    let steep = day5::VentLine::new((0, 0), (2, 6));
+    assert_eq!(steep.get_points(false), vec![]);
+    assert_eq!(steep.get_points(true), vec![(0, 0), (1, 3), (2, 6)]);
+
+    let lines = random_lines(150, 40, true);
+    let dense = day5::VentMap::new(&lines, true);
+    let sparse = day5::SparseVentMap::new(&lines, true);
+    for min_count in 1..4 {
+        assert_eq!(sparse.overlapping_count(min_count), dense.overlapping_count(min_count));
+    }
+}
+
+#[test]
+fn day5_intersections() {
+    let lines = random_lines(60, 30, true);
+    for a in &lines {
+        for b in &lines {
+            let mut expected: Vec<_> = a.get_points(true).into_iter()
+                .filter(|p| b.get_points(true).contains(p))
+                .collect();
+            let mut found = a.intersections(b);
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected, "{:?} {:?}", a, b);
+        }
+    }
+}
+
+#[test]
+fn day5_image_export() {
+    let lines = vec![
+        day5::VentLine::new((0, 0), (2, 0)),
+        day5::VentLine::new((1, 0), (1, 1)),
+    ];
+    let map = day5::VentMap::new(&lines, false);
+    assert_eq!(map.to_pgm(), "P2\n3 2\n2\n1 2 1 0 1 0\n");
+    assert!(map.to_ppm(&day5::ColourScale::grey()).starts_with("P3\n3 2\n255\n128 128 128 255 255 255"));
+    assert_eq!(map.crop((1, 0), (5, 5)), "21\n1.\n");
+    // corners given the other way round
+    assert_eq!(map.crop((5, 5), (1, 0)), "21\n1.\n");
+    assert_eq!(map.crop((2, 0), (1, 1)), "21\n1.\n");
+    assert_eq!(map.crop((4, 4), (1, 1)), "1.\n");
+
+    let scale = day5::ColourScale::parse("#000000,ff0000,ffffff").unwrap();
+    assert_eq!(scale.colour(0, 4), (0, 0, 0));
+    assert_eq!(scale.colour(2, 4), (255, 0, 0));
+    assert_eq!(scale.colour(4, 4), (255, 255, 255));
+    assert_eq!(day5::ColourScale::parse("red"), None);
+}
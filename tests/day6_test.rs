@@ -0,0 +1,104 @@
+use aoc21::days::day6::{self, Population};
+
+#[test]
+fn day6_example() {
+    let p = Population::lanternfish().with_timers(&[3, 4, 3, 1, 2]);
+    assert_eq!(p.count_on(18), Some(26));
+    assert_eq!(p.count_on(80), Some(5934));
+    assert_eq!(p.count_on(256), Some(26984457539));
+    // too many fish for a usize
+    assert_eq!(p.count_on(600), None);
+    assert_eq!(day6::lantern_count(3, 18), Some(5));
+}
+
+#[test]
+fn day6_custom_cycle() {
+    // a fish at 0 spawns every day and its young spawn the day after they appear
+    let mut p = Population::new(0, 1).with_timers(&[0]);
+    let counts: Vec<_> = (0..6).map(|_| { p = p.next_day().unwrap(); p.count().unwrap() }).collect();
+    assert_eq!(counts, vec![2, 3, 5, 8, 13, 21]);
+    assert_eq!(Population::new(0, 1).with_timers(&[0]).count_on(100), None);
+    assert_eq!(Population::new(2, 3).with_timers(&[10]).count_on(10), Some(1));
+}
+
+#[test]
+fn day6_matrix_counts() {
+    use day6::{BigUint, Modular};
+    let p = Population::lanternfish().with_timers(&[3, 4, 3, 1, 0]);
+    for days in [0, 1, 17, 80, 256] {
+        let exact = p.count_after(days, &BigUint::new(0));
+        assert_eq!(exact.to_string(), p.count_on(days as usize).unwrap().to_string());
+        let m = p.count_after(days, &Modular::new(0, 1_000_000_007));
+        assert_eq!(m.value as usize, p.count_on(days as usize).unwrap() % 1_000_000_007);
+    }
+    // well past where usize overflows
+    let big = Population::lanternfish().with_timers(&[3, 4, 3, 1, 2]).count_after(2000, &BigUint::new(0));
+    assert_eq!(big.to_string(), "25930134485516959540654250898620295097277946359266027842882316971109265815041");
+
+    // the big count reduced by hand matches the modular one
+    let m = 1_000_000_007;
+    let p = Population::lanternfish().with_timers(&[3, 4, 3, 1, 2]);
+    let exact = p.count_after(5000, &BigUint::new(0)).to_string();
+    let reduced = exact.bytes().fold(0, |acc, d| (acc * 10 + (d - b'0') as u64) % m);
+    assert_eq!(p.count_after(5000, &Modular::new(0, m)).value, reduced);
+
+    // the totals follow t(n) = t(n - 7) + t(n - 9), which gives an
+    // independent way to reach day 10^12
+    let t: Vec<u64> = (0..=40).map(|d| p.count_on(d).unwrap() as u64).collect();
+    assert!((9..=40).all(|n| t[n] == t[n - 7] + t[n - 9]));
+    assert_eq!(p.count_after(1_000_000_000_000, &Modular::new(0, m)).value, recurrence(&t[..9], 1_000_000_000_000, m));
+}
+
+// t(n) mod m from t(0..9) by squaring the companion matrix of the recurrence
+fn recurrence(first: &[u64], n: u64, m: u64) -> u64 {
+    let mul = |a: &Vec<Vec<u64>>, b: &Vec<Vec<u64>>| -> Vec<Vec<u64>> {
+        (0..9).map(|i| (0..9).map(|j| (0..9).fold(0, |acc, k| (acc + a[i][k] * b[k][j]) % m)).collect()).collect()
+    };
+    // maps (t(n), ..., t(n - 8)) to (t(n + 1), ..., t(n - 7))
+    let mut step = vec![vec![0; 9]; 9];
+    step[0][6] = 1;
+    step[0][8] = 1;
+    (1..9).for_each(|i| step[i][i - 1] = 1);
+    let mut power: Vec<Vec<u64>> = (0..9).map(|i| (0..9).map(|j| (i == j) as u64).collect()).collect();
+    let mut e = n - 8;
+    while e > 0 {
+        if e & 1 == 1 {
+            power = mul(&power, &step);
+        }
+        step = mul(&step, &step);
+        e >>= 1;
+    }
+    // the state on day 8 is (t(8), ..., t(0))
+    (0..9).fold(0, |acc, k| (acc + power[0][k] * (first[8 - k] % m)) % m)
+}
+
+#[test]
+fn day6_history_csv() {
+    let csv = Population::lanternfish().with_timers(&[3, 4, 3, 1, 2]).history_csv(2, &day6::BigUint::new(0));
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines, vec![
+        "day,t0,t1,t2,t3,t4,t5,t6,t7,t8,total,growth",
+        "0,0,1,1,2,1,0,0,0,0,5,",
+        "1,1,1,2,1,0,0,0,0,0,5,1.000000",
+        "2,1,2,1,0,0,0,1,0,1,6,1.200000",
+    ]);
+}
+
+#[test]
+fn day6_history_csv_long() {
+    use day6::{BigUint, Modular};
+    // counts well past a usize, exactly and modulo
+    let p = Population::lanternfish().with_timers(&[3, 4, 3, 1, 2]);
+    let csv = p.history_csv(600, &BigUint::new(0));
+    let last: Vec<_> = csv.lines().last().unwrap().split(',').collect();
+    assert_eq!(csv.lines().count(), 602);
+    assert_eq!(last[0], "600");
+    assert_eq!(last[10], p.count_after(600, &BigUint::new(0)).to_string());
+    assert!(last[11].starts_with("1.0"));
+
+    let m = Modular::new(0, 1_000_000_007);
+    let csv = p.history_csv(600, &m);
+    let last: Vec<_> = csv.lines().last().unwrap().split(',').collect();
+    assert_eq!(last[10], p.count_after(600, &m).to_string());
+    assert_eq!(last[11], "");
+}
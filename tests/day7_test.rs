@@ -0,0 +1,41 @@
+use aoc21::days::day7::{self, Linear, Triangular};
+
+const EXAMPLE: [usize; 10] = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+
+fn brute_force(crabs: &[usize], model: &impl day7::FuelCost) -> usize {
+    (0..=*crabs.iter().max().unwrap()).map(|p| day7::total_cost(crabs, p, model)).min().unwrap()
+}
+
+#[test]
+fn day7_example() {
+    assert_eq!(day7::align(&EXAMPLE, &Linear), (2, 37));
+    assert_eq!(day7::align(&EXAMPLE, &Triangular), (5, 168));
+}
+
+#[test]
+fn day7_custom_cost() {
+    let square = |d: usize| d * d;
+    assert_eq!(day7::align(&EXAMPLE, &square).1, brute_force(&EXAMPLE, &square));
+    let crabs = [1000, 1001, 5000, 3];
+    assert_eq!(day7::align(&crabs, &square).1, brute_force(&crabs, &square));
+    assert_eq!(day7::align(&crabs, &Triangular).1, brute_force(&crabs, &Triangular));
+    assert_eq!(day7::align(&crabs, &Linear).1, brute_force(&crabs, &Linear));
+}
+
+#[test]
+fn day7_cost_curve() {
+    let curve = day7::cost_curve(&EXAMPLE, &Linear);
+    assert_eq!(curve.len(), 17);
+    assert_eq!(curve[2], (2, 37));
+    assert_eq!(day7::within(&curve, 0.0), (2, 2));
+    assert_eq!(day7::within(&curve, 10.0), (2, 3));
+
+    let csv = day7::curve_csv(&curve, 10.0);
+    assert_eq!(csv.lines().nth(3), Some("2,37,1,1"));
+    assert_eq!(csv.lines().nth(4), Some("3,39,0,1"));
+    assert_eq!(csv.lines().nth(5), Some("4,41,0,0"));
+
+    let plot = day7::curve_plot(&curve, 10.0, 17, 5);
+    assert!(plot.lines().nth(4).unwrap().ends_with("|  O"));
+    assert!(plot.contains("Minimum 37 at [2], within 10%: 2..=3"));
+}
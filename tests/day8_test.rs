@@ -0,0 +1,59 @@
+use std::fs;
+use aoc21::days::day8::{DisplayTable, Entry, MappingError, SevenSegment};
+
+#[test]
+fn day8_example() {
+    let table = DisplayTable::seven_segment();
+    let total: usize = fs::read_to_string("data/day8example.txt").unwrap().lines()
+        .map(|l| Entry::parse(l, &table).unwrap().number())
+        .sum();
+    assert_eq!(total, 61229);
+}
+
+#[test]
+fn day8_partial_patterns() {
+    let table = DisplayTable::seven_segment();
+    let line = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
+    assert_eq!(Entry::parse(line, &table).unwrap().number(), 5353);
+    // dropping the 1 and the 4 still pins the wiring down
+    let partial = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb cagedb | cdfeb fcadb cdfeb cdbaf";
+    assert_eq!(Entry::parse(partial, &table).unwrap().number(), 5353);
+
+    let patterns: Vec<_> = ["ab", "dab"].iter().map(|p| SevenSegment::new(p)).collect();
+    assert!(matches!(Entry::create_mapping(&patterns, &table), Err(MappingError::Ambiguous(m)) if m.len() == 2));
+    let patterns: Vec<_> = ["ab", "abc", "bcd"].iter().map(|p| SevenSegment::new(p)).collect();
+    assert_eq!(Entry::create_mapping(&patterns, &table), Err(MappingError::Inconsistent));
+    assert_eq!(Entry::parse("ab abx |", &table), Err(MappingError::UnknownSegment('x')));
+}
+
+#[test]
+fn day8_custom_table() {
+    // a three segment display showing 0, 1 and 2
+    let table = DisplayTable::new("xyz", &[('0', "x"), ('1', "xy"), ('2', "xyz")]);
+    let entry = Entry::parse("z zy zyx | zy z", &table).unwrap();
+    assert_eq!(entry.number(), 10);
+}
+
+// the input line for a display wired through `wiring` (segment i -> wiring[i])
+fn scramble(table: &DisplayTable, segments: &str, wiring: &str, shown: &str) -> String {
+    let wire = |c: char| wiring.chars().nth(segments.find(c).unwrap()).unwrap();
+    let encode = |g: char| table.glyph(g).unwrap().to_string().chars().map(wire).collect::<String>();
+    let patterns: Vec<_> = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().map(encode).collect();
+    let outputs: Vec<_> = shown.chars().map(encode).collect();
+    format!("{} | {}", patterns.join(" "), outputs.join(" "))
+}
+
+#[test]
+fn day8_alphanumeric_displays() {
+    let table = DisplayTable::fourteen_segment();
+    let line = scramble(&table, "abcdefghijklmn", "dkbnaglcmfjieh", "HELLO2021");
+    let entry = Entry::parse(&line, &table).unwrap();
+    assert_eq!(entry.decode(), "HELLO2021");
+
+    let table = DisplayTable::sixteen_segment();
+    let line = scramble(&table, "abcdefghkmnprstu", "utsrpnmkhgfedcba", "XMAS");
+    let entry = Entry::parse(&line, &table).unwrap();
+    assert_eq!(entry.decode(), "XMAS");
+    assert_eq!(table.render(&[table.glyph('X').unwrap()]),
+        "\n \\   /\n  \\ /\n\n  / \\\n /   \\\n\n");
+}
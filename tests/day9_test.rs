@@ -0,0 +1,62 @@
+use std::fs;
+use aoc21::days::day9::HeightMap;
+
+#[test]
+fn day9_example_basins() {
+    let hm = HeightMap::parse(&fs::read_to_string("data/day9example.txt").unwrap());
+    let basins = hm.basins(9, false);
+    let sizes: Vec<_> = basins.basins.iter().map(|b| b.size).collect();
+    assert_eq!(sizes, vec![3, 9, 14, 9]);
+    assert_eq!(basins.basins[1].low_point, (0, 9));
+    assert_eq!(basins.basins[1].bounds, ((0, 5), (2, 9)));
+    assert_eq!(basins.labels[0][0], Some(0));
+    assert_eq!(basins.labels[0][2], None);
+}
+
+#[test]
+fn day9_connectivity_and_walls() {
+    let hm = HeightMap::parse("090\n909\n090");
+    assert_eq!(hm.basins(9, false).basins.len(), 5);
+    assert_eq!(hm.basins(9, true).basins.len(), 1);
+    assert_eq!(hm.basins(10, false).basins.len(), 1);
+}
+
+#[test]
+fn day9_large_map() {
+    // a single basin far too big for a recursive fill
+    let row = "1".repeat(1000);
+    let hm = HeightMap::parse(&vec![row; 1000].join("\n"));
+    let basins = hm.basins(9, false);
+    assert_eq!(basins.basins.len(), 1);
+    assert_eq!(basins.basins[0].size, 1_000_000);
+    assert_eq!(basins.basins[0].bounds, ((0, 0), (999, 999)));
+}
+
+#[test]
+fn day9_render() {
+    let hm = HeightMap::parse(&fs::read_to_string("data/day9example.txt").unwrap());
+    let basins = hm.basins(9, false);
+    let colours = basins.colours();
+    assert_eq!(colours, vec![0, 1, 2, 3]);
+    let plain = hm.render(&basins, false);
+    assert_eq!(plain.lines().next(), Some("aA###bbbbB"));
+    assert_eq!(plain.matches(char::is_uppercase).count(), 4);
+    let coloured = hm.render(&basins, true);
+    assert!(coloured.contains("\x1b[2m9\x1b[0m"));
+    assert_eq!(coloured.matches('*').count(), 4);
+    let ppm = hm.to_ppm(&basins);
+    assert!(ppm.starts_with("P3\n10 5\n255\n"));
+    assert!(ppm.lines().all(|l| l.len() <= 70));
+}
+
+#[test]
+fn day9_colours_cycle() {
+    // a row of single cell basins, more than there are colours
+    let hm = HeightMap::parse(&vec!["0"; 30].join("9"));
+    let colours = hm.basins(9, false).colours();
+    assert_eq!(colours.len(), 30);
+    assert_eq!(colours[..12].to_vec(), (0..12).collect::<Vec<_>>());
+    assert!(colours.windows(2).all(|w| w[0] != w[1]));
+    let uses: Vec<usize> = (0..12).map(|c| colours.iter().filter(|&&o| o == c).count()).collect();
+    assert!(uses.iter().all(|&u| u == 2 || u == 3));
+}